@@ -0,0 +1,24 @@
+//! Timing helpers shared by the benchmarks.
+//!
+//! The benchmarks use a plain `main` so they can run on stable with
+//! `cargo bench`, and this stands in for the unstable `test::Bencher`.
+
+use std::time::Instant;
+
+/// Number of timed runs averaged for each benchmark.
+pub const ITERS: u32 = 5;
+
+/// Runs `f` once to warm up and then `ITERS` more times, printing the average
+/// time of a run and of each of the `ops` operations it performs.
+pub fn bench<F: FnMut()>(name: &str, ops: u64, mut f: F) {
+    f();
+    let start = Instant::now();
+    for _ in 0..ITERS {
+        f();
+    }
+    let elapsed = start.elapsed() / ITERS;
+    let nanos = elapsed.as_secs() * 1_000_000_000 +
+                elapsed.subsec_nanos() as u64;
+    println!("{:<24} {:>10} ns/iter {:>8} ns/op",
+             name, nanos, nanos / ops);
+}
@@ -0,0 +1,72 @@
+//! Benchmarks for sending messages to an event loop from other threads.
+//!
+//! Each message is a `Remote::spawn` of a trivial task, so this measures how
+//! quickly the event loop's message queue moves work from the threads
+//! producing it onto the event loop, with varying numbers of producers.
+
+extern crate futures;
+extern crate tokio_core;
+
+mod common;
+
+use std::sync::{Arc, Barrier, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use futures::sync::oneshot::{self, Sender};
+use tokio_core::reactor::Core;
+
+use common::bench;
+
+const MESSAGES: usize = 100_000;
+
+// Counts down the messages received, completing `done` with the last one.
+struct Countdown {
+    left: AtomicUsize,
+    done: Mutex<Option<Sender<()>>>,
+}
+
+impl Countdown {
+    fn finish(&self) {
+        if self.left.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.done.lock().unwrap().take().unwrap().send(()).unwrap();
+        }
+    }
+}
+
+fn remote_spawn(core: &mut Core, threads: usize) {
+    let (tx, rx) = oneshot::channel();
+    let countdown = Arc::new(Countdown {
+        left: AtomicUsize::new(MESSAGES),
+        done: Mutex::new(Some(tx)),
+    });
+    let barrier = Arc::new(Barrier::new(threads));
+    let per_thread = MESSAGES / threads;
+    let handles = (0..threads).map(|_| {
+        let remote = core.remote();
+        let countdown = countdown.clone();
+        let barrier = barrier.clone();
+        thread::spawn(move || {
+            barrier.wait();
+            for _ in 0..per_thread {
+                let countdown = countdown.clone();
+                remote.spawn(move |_| {
+                    countdown.finish();
+                    Ok(())
+                }).unwrap();
+            }
+        })
+    }).collect::<Vec<_>>();
+    core.run(rx).unwrap();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+fn main() {
+    let mut core = Core::new().unwrap();
+    let ops = MESSAGES as u64;
+    bench("remote_spawn_1", ops, || remote_spawn(&mut core, 1));
+    bench("remote_spawn_4", ops, || remote_spawn(&mut core, 4));
+    bench("remote_spawn_16", ops, || remote_spawn(&mut core, 16));
+}
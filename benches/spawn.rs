@@ -0,0 +1,138 @@
+//! Benchmarks for spawning and waking tasks on an event loop.
+
+extern crate futures;
+extern crate tokio_core;
+
+mod common;
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+
+use futures::{Future, Poll, Async};
+use futures::sync::oneshot;
+use futures::task;
+use tokio_core::reactor::Core;
+
+use common::bench;
+
+// Resolves once `count` reaches zero, being woken up by the last task.
+struct Countdown {
+    count: Rc<Cell<usize>>,
+    waiter: Rc<Cell<Option<task::Task>>>,
+}
+
+impl Future for Countdown {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        if self.count.get() == 0 {
+            Ok(Async::Ready(()))
+        } else {
+            self.waiter.set(Some(task::current()));
+            Ok(Async::NotReady)
+        }
+    }
+}
+
+fn countdown(n: usize) -> (Countdown, Rc<Cell<usize>>, Rc<Cell<Option<task::Task>>>) {
+    let count = Rc::new(Cell::new(n));
+    let waiter = Rc::new(Cell::new(None));
+    let cd = Countdown { count: count.clone(), waiter: waiter.clone() };
+    (cd, count, waiter)
+}
+
+fn finish(count: &Cell<usize>, waiter: &Cell<Option<task::Task>>) {
+    count.set(count.get() - 1);
+    if count.get() == 0 {
+        if let Some(t) = waiter.take() {
+            t.notify();
+        }
+    }
+}
+
+// A task which wakes itself up `n` times before completing.
+struct Yield {
+    left: usize,
+    count: Rc<Cell<usize>>,
+    waiter: Rc<Cell<Option<task::Task>>>,
+}
+
+impl Future for Yield {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        if self.left == 0 {
+            finish(&self.count, &self.waiter);
+            return Ok(Async::Ready(()))
+        }
+        self.left -= 1;
+        task::current().notify();
+        Ok(Async::NotReady)
+    }
+}
+
+fn spawn_many(core: &mut Core, n: usize) {
+    let (cd, count, waiter) = countdown(n);
+    let handle = core.handle();
+    for _ in 0..n {
+        let count = count.clone();
+        let waiter = waiter.clone();
+        handle.spawn(futures::lazy(move || {
+            finish(&count, &waiter);
+            Ok(())
+        }));
+    }
+    core.run(cd).unwrap();
+}
+
+fn yield_many(core: &mut Core, tasks: usize, yields: usize) {
+    let (cd, count, waiter) = countdown(tasks);
+    let handle = core.handle();
+    for _ in 0..tasks {
+        handle.spawn(Yield {
+            left: yields,
+            count: count.clone(),
+            waiter: waiter.clone(),
+        });
+    }
+    core.run(cd).unwrap();
+}
+
+fn remote_wakeups(core: &mut Core, n: usize) {
+    let mut rxs = Vec::with_capacity(n);
+    let mut txs = Vec::with_capacity(n);
+    for _ in 0..n {
+        let (tx, rx) = oneshot::channel::<()>();
+        txs.push(tx);
+        rxs.push(rx);
+    }
+    let (cd, count, waiter) = countdown(n);
+    let handle = core.handle();
+    for rx in rxs {
+        let count = count.clone();
+        let waiter = waiter.clone();
+        handle.spawn(rx.then(move |_| {
+            finish(&count, &waiter);
+            Ok(())
+        }));
+    }
+    let t = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(1));
+        for tx in txs {
+            tx.send(()).unwrap();
+        }
+    });
+    core.run(cd).unwrap();
+    t.join().unwrap();
+}
+
+fn main() {
+    let mut core = Core::new().unwrap();
+    bench("spawn_many", 10_000, || spawn_many(&mut core, 10_000));
+    bench("yield_many", 100_000, || yield_many(&mut core, 100, 1_000));
+    bench("remote_wakeups", 10_000, || remote_wakeups(&mut core, 10_000));
+}
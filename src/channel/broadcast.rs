@@ -0,0 +1,214 @@
+//! Broadcast channels, delivering every message to many receivers.
+//!
+//! This module contains the `BroadcastSender` and `BroadcastReceiver` pair
+//! types created by the `broadcast` function. The most recent messages are
+//! kept in a buffer of fixed capacity shared by all receivers, and a receiver
+//! which falls too far behind skips the messages it missed.
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use futures::{Poll, Async};
+use futures::stream::Stream;
+use mio;
+
+use reactor::{Handle, PollEvented};
+use super::{closed, Receivers};
+
+/// The transmission half of a broadcast channel, created by the
+/// [`broadcast`] function.
+///
+/// Every message sent is delivered to all receivers of the channel. A
+/// `BroadcastSender` can be `clone`d and used from any thread.
+///
+/// [`broadcast`]: fn.broadcast.html
+pub struct BroadcastSender<T> {
+    inner: Arc<Mutex<Inner<T>>>,
+}
+
+/// The receiving half of a broadcast channel.
+///
+/// This type implements the `Stream` trait, yielding a clone of each message
+/// sent on the channel after the receiver was created. The stream ends once
+/// all senders are gone and every message has been received.
+///
+/// If a receiver falls more than the channel's capacity behind its senders,
+/// the messages it missed are dropped and the stream yields a `Lagged` error.
+/// Polling the stream again continues with the oldest message still kept.
+pub struct BroadcastReceiver<T> {
+    id: usize,
+    io: PollEvented<mio::Registration>,
+    inner: Arc<Mutex<Inner<T>>>,
+
+    // Sequence number of the next message this receiver will see.
+    next: u64,
+}
+
+/// An error yielded by a `BroadcastReceiver` which fell too far behind and
+/// missed some messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lagged {
+    skipped: u64,
+}
+
+struct Inner<T> {
+    // The last `capacity` messages sent, the first of which has the sequence
+    // number `head`.
+    buffer: VecDeque<T>,
+    capacity: usize,
+    head: u64,
+    senders: usize,
+    receivers: Receivers,
+}
+
+/// Creates a new broadcast channel, where each message is received by every
+/// receiver.
+///
+/// The channel keeps the last `capacity` messages sent around for receivers
+/// which haven't seen them yet. Senders are never blocked, so a receiver
+/// which isn't keeping up misses out on messages rather than holding up
+/// everyone else, and is told so through a `Lagged` error.
+///
+/// The returned receiver is registered with the event loop of `handle`, and
+/// more receivers can be created, on any event loop, with
+/// `BroadcastSender::subscribe`.
+///
+/// # Panics
+///
+/// This function panics if `capacity` is zero.
+pub fn broadcast<T>(capacity: usize, handle: &Handle)
+                    -> io::Result<(BroadcastSender<T>, BroadcastReceiver<T>)>
+    where T: Clone + Send + 'static,
+{
+    assert!(capacity > 0, "a broadcast channel needs a capacity of at least 1");
+    let tx = BroadcastSender {
+        inner: Arc::new(Mutex::new(Inner {
+            buffer: VecDeque::with_capacity(capacity),
+            capacity: capacity,
+            head: 0,
+            senders: 1,
+            receivers: Receivers::new(),
+        })),
+    };
+    let rx = try!(tx.subscribe(handle));
+    Ok((tx, rx))
+}
+
+impl<T> BroadcastSender<T> {
+    /// Sends a message to all receivers of this channel.
+    ///
+    /// This never blocks: if the channel is full the oldest message is
+    /// dropped to make room. An error of the kind `BrokenPipe` is returned if
+    /// there are no receivers left.
+    pub fn send(&self, t: T) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.receivers.len() == 0 {
+            return Err(closed())
+        }
+        if inner.buffer.len() == inner.capacity {
+            inner.buffer.pop_front();
+            inner.head += 1;
+        }
+        inner.buffer.push_back(t);
+        inner.receivers.notify_all();
+        Ok(())
+    }
+
+    /// Creates a new receiver for this channel, registered with the event
+    /// loop of `handle`.
+    ///
+    /// The receiver only sees messages sent after it was created.
+    pub fn subscribe(&self, handle: &Handle)
+                     -> io::Result<BroadcastReceiver<T>> {
+        let mut inner = self.inner.lock().unwrap();
+        let (id, io) = try!(inner.receivers.register(handle));
+        Ok(BroadcastReceiver {
+            id: id,
+            io: io,
+            inner: self.inner.clone(),
+            next: inner.head + inner.buffer.len() as u64,
+        })
+    }
+
+    /// Returns the number of receivers of this channel.
+    pub fn receiver_count(&self) -> usize {
+        self.inner.lock().unwrap().receivers.len()
+    }
+}
+
+impl<T> Clone for BroadcastSender<T> {
+    fn clone(&self) -> BroadcastSender<T> {
+        self.inner.lock().unwrap().senders += 1;
+        BroadcastSender { inner: self.inner.clone() }
+    }
+}
+
+impl<T> Drop for BroadcastSender<T> {
+    fn drop(&mut self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.senders -= 1;
+        if inner.senders == 0 {
+            inner.receivers.notify_all();
+        }
+    }
+}
+
+impl<T: Clone> Stream for BroadcastReceiver<T> {
+    type Item = T;
+    type Error = Lagged;
+
+    fn poll(&mut self) -> Poll<Option<T>, Lagged> {
+        if let Async::NotReady = self.io.poll_read() {
+            return Ok(Async::NotReady)
+        }
+        let inner = self.inner.lock().unwrap();
+        if self.next < inner.head {
+            let skipped = inner.head - self.next;
+            self.next = inner.head;
+            return Err(Lagged { skipped: skipped })
+        }
+        let index = (self.next - inner.head) as usize;
+        if let Some(t) = inner.buffer.get(index) {
+            self.next += 1;
+            return Ok(Async::Ready(Some(t.clone())))
+        }
+        if inner.senders == 0 {
+            return Ok(Async::Ready(None))
+        }
+
+        // Nothing new, so wait for a sender to flag us as readable again.
+        // This happens under the lock so we can't miss a message sent in the
+        // meantime.
+        inner.receivers.set_readable(self.id, false);
+        self.io.need_read();
+        Ok(Async::NotReady)
+    }
+}
+
+impl<T> Drop for BroadcastReceiver<T> {
+    fn drop(&mut self) {
+        self.inner.lock().unwrap().receivers.deregister(self.id);
+    }
+}
+
+impl Lagged {
+    /// Returns the number of messages the receiver missed.
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+}
+
+impl fmt::Display for Lagged {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "receiver lagged behind and missed {} messages", self.skipped)
+    }
+}
+
+impl Error for Lagged {
+    fn description(&self) -> &str {
+        "receiver lagged behind and missed messages"
+    }
+}
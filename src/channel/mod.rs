@@ -0,0 +1,538 @@
+//! In-memory evented channels.
+//!
+//! This module contains a `Sender` and `Receiver` pair types which can be used
+//! to send messages between different future tasks, along with a
+//! `BoundedSender` for channels which apply backpressure to senders. Either
+//! side can find out when the other is done: the `Receiver` ends once all
+//! senders are gone, and senders notice when the `Receiver` is closed.
+//!
+//! For sending each message to many receivers there are also [`broadcast`]
+//! channels, and [`watch`] channels for receivers which only care about the
+//! latest value.
+//!
+//! [`broadcast`]: fn.broadcast.html
+//! [`watch`]: fn.watch.html
+
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::mem;
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::TryRecvError;
+
+use futures::{Future, Poll, Async, Sink, StartSend, AsyncSink};
+use futures::stream::Stream;
+use futures::task::{self, Task};
+use mio;
+use mio::channel;
+
+use reactor::{Handle, PollEvented};
+
+mod broadcast;
+mod watch;
+
+pub use self::broadcast::{broadcast, BroadcastSender, BroadcastReceiver, Lagged};
+pub use self::watch::{watch, WatchSender, WatchReceiver};
+
+/// The transmission half of a channel used for sending messages to a receiver.
+///
+/// A `Sender` can be `clone`d to have multiple threads or instances sending
+/// messages to one receiver.
+///
+/// This type is created by the [`channel`] function.
+///
+/// [`channel`]: fn.channel.html
+pub struct Sender<T> {
+    tx: channel::Sender<T>,
+    shared: Arc<Shared>,
+}
+
+/// The receiving half of a channel used for processing messages sent by a
+/// `Sender`.
+///
+/// A `Receiver` cannot be cloned, so only one thread can receive messages at a
+/// time.
+///
+/// This type is created by the [`channel`] and [`bounded`] functions and
+/// implements the `Stream` trait to represent received messages.
+///
+/// [`channel`]: fn.channel.html
+/// [`bounded`]: fn.bounded.html
+pub struct Receiver<T> {
+    rx: PollEvented<channel::Receiver<T>>,
+    shared: Arc<Shared>,
+
+    // Whether senders may be waiting for space in the channel.
+    bounded: bool,
+}
+
+/// The transmission half of a bounded channel, created by the [`bounded`]
+/// function.
+///
+/// Messages are sent through the `Sink` implementation, which only accepts a
+/// message if there's space for it in the channel. Otherwise the sending task
+/// is parked until the `Receiver` has taken a message off the channel.
+///
+/// Like `Sender`, a `BoundedSender` can be `clone`d and used from any thread.
+///
+/// [`bounded`]: fn.bounded.html
+pub struct BoundedSender<T> {
+    id: usize,
+    tx: channel::SyncSender<T>,
+    shared: Arc<Shared>,
+
+    // Whether we've registered in `send_tasks` since last sending a message.
+    parked: bool,
+}
+
+/// A future which resolves once the `Receiver` of a channel has been closed
+/// or dropped.
+///
+/// This is created by `Sender::closed` and `BoundedSender::closed`.
+pub struct Closed {
+    id: usize,
+    shared: Arc<Shared>,
+}
+
+// State shared between both halves of a channel, on top of the underlying mio
+// channel.
+struct Shared {
+    closed: AtomicBool,
+
+    // Held for reading by senders while they check `closed` and enqueue a
+    // message, and for writing while closing, so a message can't slip in
+    // after the receiver has been closed and drained.
+    sending: RwLock<()>,
+    next_id: AtomicUsize,
+
+    // Tasks waiting on a `Closed` future, keyed by the future's id.
+    closed_tasks: Mutex<HashMap<usize, Task>>,
+
+    // Senders of a bounded channel waiting for space in the channel, in the
+    // order they started waiting and keyed by the sender's id. Each sender
+    // has at most one entry.
+    send_tasks: Mutex<VecDeque<(usize, Task)>>,
+}
+
+/// Creates a new in-memory channel used for sending data across `Send +
+/// 'static` boundaries, frequently threads.
+///
+/// This type can be used to conveniently send messages between futures.
+/// Unlike the futures crate `channel` method and types, the returned tx/rx
+/// pair is a multi-producer single-consumer (mpsc) channel *with no
+/// backpressure*. Currently it's left up to the application to implement a
+/// mechanism, if necessary, to avoid messages piling up.
+///
+/// The returned `Sender` can be used to send messages that are processed by
+/// the returned `Receiver`. The `Sender` can be cloned to send messages
+/// from multiple sources simultaneously.
+pub fn channel<T>(handle: &Handle) -> io::Result<(Sender<T>, Receiver<T>)>
+    where T: Send + 'static,
+{
+    let (tx, rx) = channel::channel();
+    let rx = try!(PollEvented::new(rx, handle));
+    let shared = Arc::new(Shared::new());
+    let tx = Sender { tx: tx, shared: shared.clone() };
+    Ok((tx, Receiver { rx: rx, shared: shared, bounded: false }))
+}
+
+/// Creates a new in-memory channel which holds at most `capacity` messages.
+///
+/// This is the same as `channel` except that the channel provides
+/// backpressure: once `capacity` messages are waiting to be received, the
+/// returned `BoundedSender` won't accept any more until the `Receiver` has
+/// processed some of them. Tasks sending messages are parked in the meantime,
+/// and woken up as the `Receiver` drains the channel.
+///
+/// # Panics
+///
+/// This function panics if `capacity` is zero.
+pub fn bounded<T>(capacity: usize, handle: &Handle)
+                  -> io::Result<(BoundedSender<T>, Receiver<T>)>
+    where T: Send + 'static,
+{
+    assert!(capacity > 0, "a bounded channel needs a capacity of at least 1");
+    let (tx, rx) = channel::sync_channel(capacity);
+    let rx = try!(PollEvented::new(rx, handle));
+    let shared = Arc::new(Shared::new());
+    let tx = BoundedSender {
+        id: shared.next_id.fetch_add(1, Ordering::Relaxed),
+        tx: tx,
+        shared: shared.clone(),
+        parked: false,
+    };
+    Ok((tx, Receiver { rx: rx, shared: shared, bounded: true }))
+}
+
+fn disconnected() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "channel has been disconnected")
+}
+
+fn closed() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "channel has been closed")
+}
+
+impl<T> Sender<T> {
+    /// Sends a message to the corresponding receiver of this sender.
+    ///
+    /// The message provided will be enqueued on the channel immediately, and
+    /// this function will return immediately. Keep in mind that the
+    /// underlying channel has infinite capacity, and this may not always be
+    /// desired.
+    ///
+    /// If an I/O error happens while sending the message, or if the receiver
+    /// has gone away, then an error will be returned. Note that I/O errors here
+    /// are generally quite abnormal. Once the receiver has been closed, the
+    /// error returned is of the kind `BrokenPipe`.
+    pub fn send(&self, t: T) -> io::Result<()> {
+        let _sending = self.shared.sending.read().unwrap();
+        if self.is_closed() {
+            return Err(closed())
+        }
+        self.tx.send(t).map_err(|e| {
+            match e {
+                channel::SendError::Io(e) => e,
+                channel::SendError::Disconnected(_) => disconnected(),
+            }
+        })
+    }
+
+    /// Returns whether the receiver of this channel has been closed or
+    /// dropped, in which case no more messages can be sent.
+    pub fn is_closed(&self) -> bool {
+        self.shared.is_closed()
+    }
+
+    /// Returns a future which resolves once the receiver of this channel has
+    /// been closed or dropped.
+    ///
+    /// This can be used by producers to find out they should stop producing
+    /// messages even while they have nothing to send.
+    pub fn closed(&self) -> Closed {
+        closed_future(&self.shared)
+    }
+}
+
+/// Sending through the `Sink` implementation never blocks, as the channel is
+/// unbounded, so it's mostly useful for combinators like `Stream::forward`.
+///
+/// Note that with `Sink` in scope, calling `send` on an owned `Sender`
+/// resolves to `Sink::send`; call it through a reference, as in
+/// `(&tx).send(t)`, to send a message right away instead.
+impl<T> Sink for Sender<T> {
+    type SinkItem = T;
+    type SinkError = io::Error;
+
+    fn start_send(&mut self, t: T) -> StartSend<T, io::Error> {
+        try!(Sender::send(self, t));
+        Ok(AsyncSink::Ready)
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), io::Error> {
+        Ok(Async::Ready(()))
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Sender<T> {
+        Sender {
+            tx: self.tx.clone(),
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> BoundedSender<T> {
+    /// Returns whether the receiver of this channel has been closed or
+    /// dropped, in which case no more messages can be sent.
+    pub fn is_closed(&self) -> bool {
+        self.shared.is_closed()
+    }
+
+    /// Returns a future which resolves once the receiver of this channel has
+    /// been closed or dropped.
+    pub fn closed(&self) -> Closed {
+        closed_future(&self.shared)
+    }
+
+    // Stops waiting for space in the channel once we've sent a message, so a
+    // stale entry doesn't swallow the wakeup meant for another sender.
+    fn stop_waiting(&mut self) {
+        if self.parked {
+            self.parked = false;
+            self.shared.remove_sender(self.id);
+        }
+    }
+
+    fn try_send(&self, t: T) -> StartSend<T, io::Error> {
+        let _sending = self.shared.sending.read().unwrap();
+        if self.is_closed() {
+            return Err(closed())
+        }
+        match self.tx.try_send(t) {
+            Ok(()) => Ok(AsyncSink::Ready),
+            Err(channel::TrySendError::Full(t)) => Ok(AsyncSink::NotReady(t)),
+            Err(channel::TrySendError::Io(e)) => Err(e),
+            Err(channel::TrySendError::Disconnected(_)) => Err(disconnected()),
+        }
+    }
+}
+
+impl<T> Sink for BoundedSender<T> {
+    type SinkItem = T;
+    type SinkError = io::Error;
+
+    fn start_send(&mut self, t: T) -> StartSend<T, io::Error> {
+        let t = match try!(self.try_send(t)) {
+            AsyncSink::Ready => {
+                self.stop_waiting();
+                return Ok(AsyncSink::Ready)
+            }
+            AsyncSink::NotReady(t) => t,
+        };
+        self.shared.park_sender(self.id);
+        self.parked = true;
+
+        // The receiver may have made space, or been closed, before we were
+        // parked, in which case nobody is going to wake us up. Try again now
+        // that we're registered.
+        let res = try!(self.try_send(t));
+        if let AsyncSink::Ready = res {
+            self.stop_waiting();
+        }
+        Ok(res)
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), io::Error> {
+        Ok(Async::Ready(()))
+    }
+}
+
+impl<T> Clone for BoundedSender<T> {
+    fn clone(&self) -> BoundedSender<T> {
+        BoundedSender {
+            id: self.shared.next_id.fetch_add(1, Ordering::Relaxed),
+            tx: self.tx.clone(),
+            shared: self.shared.clone(),
+            parked: false,
+        }
+    }
+}
+
+impl<T> Drop for BoundedSender<T> {
+    fn drop(&mut self) {
+        // If we were woken up for space in the channel which we're now never
+        // going to use, pass the wakeup on to the next sender.
+        if self.parked && !self.shared.remove_sender(self.id) {
+            self.shared.unpark_sender();
+        }
+    }
+}
+
+impl Future for Closed {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        if self.shared.is_closed() {
+            return Ok(Async::Ready(()))
+        }
+        self.shared.closed_tasks.lock().unwrap().insert(self.id, task::park());
+
+        // Check again now that we're registered, in case the receiver was
+        // closed in the meantime and we missed being woken up.
+        if self.shared.is_closed() {
+            self.shared.closed_tasks.lock().unwrap().remove(&self.id);
+            Ok(Async::Ready(()))
+        } else {
+            Ok(Async::NotReady)
+        }
+    }
+}
+
+impl Drop for Closed {
+    fn drop(&mut self) {
+        self.shared.closed_tasks.lock().unwrap().remove(&self.id);
+    }
+}
+
+impl Shared {
+    fn new() -> Shared {
+        Shared {
+            closed: AtomicBool::new(false),
+            sending: RwLock::new(()),
+            next_id: AtomicUsize::new(0),
+            closed_tasks: Mutex::new(HashMap::new()),
+            send_tasks: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    fn close(&self) {
+        {
+            let _sending = self.sending.write().unwrap();
+            if self.closed.swap(true, Ordering::SeqCst) {
+                return
+            }
+        }
+        let waiters = mem::replace(&mut *self.closed_tasks.lock().unwrap(),
+                                   HashMap::new());
+        for (_, task) in waiters {
+            task.unpark();
+        }
+        let senders = mem::replace(&mut *self.send_tasks.lock().unwrap(),
+                                   VecDeque::new());
+        for (_, task) in senders {
+            task.unpark();
+        }
+    }
+
+    // Registers the current task as waiting on behalf of sender `id` for
+    // space in the channel, replacing the sender's previous task if any.
+    fn park_sender(&self, id: usize) {
+        let mut tasks = self.send_tasks.lock().unwrap();
+        let task = task::park();
+        for entry in tasks.iter_mut() {
+            if entry.0 == id {
+                entry.1 = task;
+                return
+            }
+        }
+        tasks.push_back((id, task));
+    }
+
+    // Removes sender `id` from the senders waiting for space, returning
+    // whether it was still waiting.
+    fn remove_sender(&self, id: usize) -> bool {
+        let mut tasks = self.send_tasks.lock().unwrap();
+        let len = tasks.len();
+        tasks.retain(|&(other, _)| other != id);
+        tasks.len() != len
+    }
+
+    // Wakes up the sender which has been waiting the longest, as there's room
+    // for one more message in the channel.
+    fn unpark_sender(&self) {
+        let task = self.send_tasks.lock().unwrap().pop_front();
+        if let Some((_, task)) = task {
+            task.unpark();
+        }
+    }
+}
+
+fn closed_future(shared: &Arc<Shared>) -> Closed {
+    Closed {
+        id: shared.next_id.fetch_add(1, Ordering::Relaxed),
+        shared: shared.clone(),
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Closes the receiving half of the channel without dropping it.
+    ///
+    /// Any further attempt to send a message on the channel fails with an
+    /// error of the kind `BrokenPipe`, and senders waiting on `closed` are
+    /// woken up. Messages which were already sent can still be received, after
+    /// which the stream ends.
+    pub fn close(&mut self) {
+        self.shared.close();
+    }
+}
+
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<T>, io::Error> {
+        // Once closed we're just draining what's left, so don't wait for
+        // readiness which may never come.
+        if !self.shared.is_closed() {
+            if let Async::NotReady = self.rx.poll_read() {
+                return Ok(Async::NotReady)
+            }
+        }
+        match self.rx.get_ref().try_recv() {
+            Ok(t) => {
+                // Wake up a sender waiting for the space we just made.
+                if self.bounded {
+                    self.shared.unpark_sender();
+                }
+                Ok(Async::Ready(Some(t)))
+            }
+            Err(TryRecvError::Empty) => {
+                if self.shared.is_closed() {
+                    return Ok(Async::Ready(None))
+                }
+                self.rx.need_read();
+                Ok(Async::NotReady)
+            }
+            Err(TryRecvError::Disconnected) => Ok(Async::Ready(None)),
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.close();
+    }
+}
+
+// The receivers of a broadcast or watch channel. Each of them is registered
+// with its event loop through a `mio::Registration`, whose readiness is set by
+// senders when there's something new to receive.
+struct Receivers {
+    next_id: usize,
+    readiness: HashMap<usize, mio::SetReadiness>,
+}
+
+impl Receivers {
+    fn new() -> Receivers {
+        Receivers {
+            next_id: 0,
+            readiness: HashMap::new(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.readiness.len()
+    }
+
+    // Registers a new receiver with the event loop of `handle`, returning its
+    // id and the registration to poll.
+    fn register(&mut self, handle: &Handle)
+                -> io::Result<(usize, PollEvented<mio::Registration>)> {
+        let (registration, set_readiness) = mio::Registration::new2();
+        let io = try!(PollEvented::new(registration, handle));
+        let id = self.next_id;
+        self.next_id += 1;
+        self.readiness.insert(id, set_readiness);
+        Ok((id, io))
+    }
+
+    fn deregister(&mut self, id: usize) {
+        self.readiness.remove(&id);
+    }
+
+    // Flags the receiver `id` as readable, or not.
+    fn set_readable(&self, id: usize, readable: bool) {
+        let ready = if readable {
+            mio::Ready::readable()
+        } else {
+            mio::Ready::empty()
+        };
+        if let Some(set_readiness) = self.readiness.get(&id) {
+            drop(set_readiness.set_readiness(ready));
+        }
+    }
+
+    fn notify_all(&self) {
+        for set_readiness in self.readiness.values() {
+            if !set_readiness.readiness().is_readable() {
+                drop(set_readiness.set_readiness(mio::Ready::readable()));
+            }
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! Watch channels, publishing a single value which changes over time.
+//!
+//! This module contains the `WatchSender` and `WatchReceiver` pair types
+//! created by the `watch` function. Receivers are notified when the value
+//! changes, but only ever see the latest one.
+
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use futures::{Poll, Async};
+use futures::stream::Stream;
+use mio;
+
+use reactor::{Handle, PollEvented};
+use super::{closed, Receivers};
+
+/// The transmission half of a watch channel, created by the [`watch`]
+/// function.
+///
+/// A `WatchSender` can be `clone`d and used from any thread.
+///
+/// [`watch`]: fn.watch.html
+pub struct WatchSender<T> {
+    inner: Arc<Mutex<Inner<T>>>,
+}
+
+/// The receiving half of a watch channel.
+///
+/// This type implements the `Stream` trait, yielding a clone of the
+/// channel's value whenever it has changed since the receiver last saw it,
+/// starting with the value at the time the receiver was created. Values which
+/// are replaced before the receiver gets to them are never seen. The stream
+/// ends once all senders are gone.
+pub struct WatchReceiver<T> {
+    id: usize,
+    io: PollEvented<mio::Registration>,
+    inner: Arc<Mutex<Inner<T>>>,
+
+    // The version of the value this receiver has last seen, if any.
+    seen: Option<u64>,
+}
+
+struct Inner<T> {
+    value: T,
+    version: u64,
+    senders: usize,
+    receivers: Receivers,
+}
+
+/// Creates a new watch channel holding the value `initial`.
+///
+/// A watch channel only ever holds a single value, which senders replace and
+/// receivers are notified of. This is useful for things like configuration,
+/// where a receiver only needs to know what the latest value is.
+///
+/// The returned receiver is registered with the event loop of `handle`, and
+/// more receivers can be created, on any event loop, with
+/// `WatchSender::subscribe`.
+pub fn watch<T>(initial: T, handle: &Handle)
+                -> io::Result<(WatchSender<T>, WatchReceiver<T>)>
+    where T: Clone + Send + 'static,
+{
+    let tx = WatchSender {
+        inner: Arc::new(Mutex::new(Inner {
+            value: initial,
+            version: 0,
+            senders: 1,
+            receivers: Receivers::new(),
+        })),
+    };
+    let rx = try!(tx.subscribe(handle));
+    Ok((tx, rx))
+}
+
+impl<T> WatchSender<T> {
+    /// Replaces the value of this channel, notifying all receivers.
+    ///
+    /// An error of the kind `BrokenPipe` is returned if there are no
+    /// receivers left.
+    pub fn send(&self, t: T) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.receivers.len() == 0 {
+            return Err(closed())
+        }
+        inner.value = t;
+        inner.version += 1;
+        inner.receivers.notify_all();
+        Ok(())
+    }
+
+    /// Creates a new receiver for this channel, registered with the event
+    /// loop of `handle`.
+    ///
+    /// The receiver first yields the current value of the channel.
+    pub fn subscribe(&self, handle: &Handle) -> io::Result<WatchReceiver<T>> {
+        let mut inner = self.inner.lock().unwrap();
+        let (id, io) = try!(inner.receivers.register(handle));
+        inner.receivers.set_readable(id, true);
+        Ok(WatchReceiver {
+            id: id,
+            io: io,
+            inner: self.inner.clone(),
+            seen: None,
+        })
+    }
+}
+
+impl<T> Clone for WatchSender<T> {
+    fn clone(&self) -> WatchSender<T> {
+        self.inner.lock().unwrap().senders += 1;
+        WatchSender { inner: self.inner.clone() }
+    }
+}
+
+impl<T> Drop for WatchSender<T> {
+    fn drop(&mut self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.senders -= 1;
+        if inner.senders == 0 {
+            inner.receivers.notify_all();
+        }
+    }
+}
+
+impl<T: Clone> WatchReceiver<T> {
+    /// Returns a clone of the current value of the channel, without marking
+    /// it as seen.
+    pub fn latest(&self) -> T {
+        self.inner.lock().unwrap().value.clone()
+    }
+}
+
+impl<T: Clone> Stream for WatchReceiver<T> {
+    type Item = T;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<T>, io::Error> {
+        if let Async::NotReady = self.io.poll_read() {
+            return Ok(Async::NotReady)
+        }
+        let inner = self.inner.lock().unwrap();
+        if self.seen != Some(inner.version) {
+            self.seen = Some(inner.version);
+            return Ok(Async::Ready(Some(inner.value.clone())))
+        }
+        if inner.senders == 0 {
+            return Ok(Async::Ready(None))
+        }
+
+        // Nothing new, so wait for a sender to flag us as readable again.
+        // This happens under the lock so we can't miss a value sent in the
+        // meantime.
+        inner.receivers.set_readable(self.id, false);
+        self.io.need_read();
+        Ok(Async::NotReady)
+    }
+}
+
+impl<T> Drop for WatchReceiver<T> {
+    fn drop(&mut self) {
+        self.inner.lock().unwrap().receivers.deregister(self.id);
+    }
+}
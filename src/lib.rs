@@ -12,7 +12,8 @@
 //! * TCP, both streams and listeners
 //! * UDP sockets
 //! * Message queues
-//! * Timeouts
+//! * Timeouts and intervals
+//! * Unix signals and child processes
 //!
 //! More functionality is likely to be added over time, but otherwise the crate
 //! is intended to be flexible with the `PollEvented` type which accepts any
@@ -96,6 +97,8 @@
 
 #[macro_use]
 extern crate futures;
+#[cfg(unix)]
+extern crate libc;
 extern crate mio;
 extern crate slab;
 
@@ -109,7 +112,11 @@ extern crate log;
 pub mod io;
 
 mod mpsc_queue;
-mod heap;
+mod ring_queue;
+mod wheel;
 pub mod channel;
 pub mod net;
+#[cfg(unix)]
+pub mod process;
 pub mod reactor;
+pub mod sync;
@@ -1,18 +1,27 @@
-//! TCP/UDP bindings for `tokio-core`
+//! TCP/UDP/Unix bindings for `tokio-core`
 //!
 //! This module contains the TCP/UDP networking types, similar to the standard
-//! library, which can be used to implement networking protocols.
+//! library, which can be used to implement networking protocols. On Unix
+//! platforms it also contains Unix domain socket types.
 
 mod tcp;
 mod udp;
 mod stream_udp;
 mod stream_tcp;
+#[cfg(unix)]
+mod unix;
+#[cfg(unix)]
+mod sys;
 
 use std::io;
 
 pub use self::tcp::{TcpStream, TcpStreamNew};
 pub use self::tcp::{TcpListener, Incoming};
 pub use self::udp::{UdpSocket};
+#[cfg(unix)]
+pub use self::unix::{UnixStream, UnixStreamConnect, UCred};
+#[cfg(unix)]
+pub use self::unix::{UnixListener, UnixIncoming, UnixDatagram};
 
 /// Implementations of futures::streams for TCP and UDP
 pub mod stream {
@@ -0,0 +1,73 @@
+//! Socket helpers shared by the Unix implementations of the networking types.
+
+use std::io;
+use std::os::unix::prelude::*;
+
+use libc;
+
+/// Creates a new close-on-exec socket, which is also put into nonblocking
+/// mode if `nonblocking` is set.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd",
+          target_os = "dragonfly", target_os = "netbsd", target_os = "openbsd"))]
+pub fn socket(family: libc::c_int, ty: libc::c_int, nonblocking: bool)
+              -> io::Result<RawFd> {
+    let mut ty = ty | libc::SOCK_CLOEXEC;
+    if nonblocking {
+        ty |= libc::SOCK_NONBLOCK;
+    }
+    let fd = unsafe { libc::socket(family, ty, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error())
+    }
+    Ok(fd)
+}
+
+// Platforms without `SOCK_CLOEXEC` have to set the flags after the fact.
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd",
+              target_os = "dragonfly", target_os = "netbsd", target_os = "openbsd")))]
+pub fn socket(family: libc::c_int, ty: libc::c_int, nonblocking: bool)
+              -> io::Result<RawFd> {
+    let fd = unsafe { libc::socket(family, ty, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error())
+    }
+    let res = set_cloexec(fd).and_then(|()| {
+        if nonblocking {
+            set_nonblocking(fd)
+        } else {
+            Ok(())
+        }
+    });
+    if let Err(e) = res {
+        unsafe {
+            libc::close(fd);
+        }
+        return Err(e)
+    }
+    Ok(fd)
+}
+
+/// Sets the close-on-exec flag on `fd`.
+pub fn set_cloexec(fd: RawFd) -> io::Result<()> {
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFD);
+        if flags < 0 ||
+           libc::fcntl(fd, libc::F_SETFD, flags | libc::FD_CLOEXEC) < 0 {
+            return Err(io::Error::last_os_error())
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd",
+              target_os = "dragonfly", target_os = "netbsd", target_os = "openbsd")))]
+fn set_nonblocking(fd: RawFd) -> io::Result<()> {
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+        if flags < 0 ||
+           libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) < 0 {
+            return Err(io::Error::last_os_error())
+        }
+    }
+    Ok(())
+}
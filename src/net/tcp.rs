@@ -8,7 +8,7 @@ use futures::{self, Future, failed, Poll, Async};
 use mio;
 
 use io::{Io, IoFuture, IoStream};
-use reactor::{Handle, PollEvented};
+use reactor::{Handle, PollEvented, ShutdownSignal};
 
 /// An I/O object representing a TCP socket listening for incoming connections.
 ///
@@ -91,10 +91,13 @@ impl TcpListener {
     /// accepts.
     ///
     /// This method returns an implementation of the `Stream` trait which
-    /// resolves to the sockets the are accepted on this listener.
+    /// resolves to the sockets the are accepted on this listener. The stream
+    /// ends once shutdown of the event loop is requested through
+    /// `Remote::shutdown`.
     pub fn incoming(self) -> Incoming {
         struct MyIncoming {
             inner: TcpListener,
+            shutdown: ShutdownSignal,
         }
 
         impl Stream for MyIncoming {
@@ -102,6 +105,10 @@ impl TcpListener {
             type Error = io::Error;
 
             fn poll(&mut self) -> Poll<Option<Self::Item>, io::Error> {
+                // Stop accepting once the event loop is shutting down.
+                if let Ok(Async::Ready(())) = self.shutdown.poll() {
+                    return Ok(Async::Ready(None))
+                }
                 if let Async::NotReady = self.inner.io.poll_read() {
                     return Ok(Async::NotReady)
                 }
@@ -117,18 +124,27 @@ impl TcpListener {
         }
 
         let remote = self.io.remote().clone();
-        let stream = MyIncoming { inner: self };
+        let stream = MyIncoming {
+            shutdown: remote.shutdown_signal(),
+            inner: self,
+        };
         Incoming {
             inner: stream.and_then(move |(tcp, addr)| {
+                // If the event loop is gone then the closure is dropped, and
+                // so is `tx`.
                 let (tx, rx) = futures::oneshot();
-                remote.spawn(move |handle| {
+                drop(remote.spawn(move |handle| {
                     let res = PollEvented::new(tcp, handle).map(move |io| {
                         (TcpStream { io: io }, addr)
                     });
                     tx.complete(res);
                     Ok(())
-                });
-                rx.then(|r| r.expect("shouldn't be canceled"))
+                }));
+                rx.then(|r| {
+                    r.unwrap_or_else(|_| {
+                        Err(io::Error::new(io::ErrorKind::Other, "event loop gone"))
+                    })
+                })
             }).boxed(),
         }
     }
@@ -461,9 +477,111 @@ impl Future for TcpStreamConnect {
 
 #[cfg(unix)]
 mod sys {
+    use std::io;
+    use std::mem;
+    use std::net::{self, SocketAddr};
     use std::os::unix::prelude::*;
+
+    use libc;
+
+    use net::sys::socket;
+    use reactor::Handle;
     use super::{TcpStream, TcpListener};
 
+    impl TcpListener {
+        /// Creates a new TCP listener bound to `addr` with the `SO_REUSEPORT`
+        /// option set, associated with the event loop that `handle` refers
+        /// to.
+        ///
+        /// Unlike `bind`, this can be called several times with the same
+        /// address, typically once for each event loop in a `CorePool`. On
+        /// Linux the kernel then distributes incoming connections across all
+        /// of the listeners, so that each event loop accepts its own share of
+        /// them. Other platforms allow several listeners to bind the same
+        /// address but may not balance connections between them.
+        ///
+        /// If `addr` has a port of 0 then each call binds a different port,
+        /// so bind the first listener to port 0 and then the rest to its
+        /// `local_addr`.
+        pub fn bind_reuseport(addr: &SocketAddr, handle: &Handle)
+                              -> io::Result<TcpListener> {
+            let listener = try!(reuseport_listener(addr));
+            let addr = try!(listener.local_addr());
+            TcpListener::from_listener(listener, &addr, handle)
+        }
+
+        /// Creates a new `TcpListener` from a raw file descriptor of a bound
+        /// and listening TCP socket, associating it with the event loop that
+        /// `handle` refers to.
+        ///
+        /// This is useful to rebuild a listener from a descriptor received
+        /// from another process, for example with `UnixStream::recv_fds`.
+        ///
+        /// This function is unsafe as the descriptor must be a valid, open
+        /// TCP socket, and ownership of it is transferred to the returned
+        /// listener.
+        pub unsafe fn from_raw_fd(fd: RawFd, handle: &Handle)
+                                  -> io::Result<TcpListener> {
+            let listener = net::TcpListener::from_raw_fd(fd);
+            let addr = try!(listener.local_addr());
+            TcpListener::from_listener(listener, &addr, handle)
+        }
+    }
+
+    fn reuseport_listener(addr: &SocketAddr) -> io::Result<net::TcpListener> {
+        unsafe {
+            let family = match *addr {
+                SocketAddr::V4(..) => libc::AF_INET,
+                SocketAddr::V6(..) => libc::AF_INET6,
+            };
+            let fd = try!(socket(family, libc::SOCK_STREAM, false));
+            let listener = net::TcpListener::from_raw_fd(fd);
+            try!(setsockopt(fd, libc::SO_REUSEADDR, 1));
+            try!(setsockopt(fd, libc::SO_REUSEPORT, 1));
+
+            let ret = match *addr {
+                SocketAddr::V4(ref a) => {
+                    let mut sin: libc::sockaddr_in = mem::zeroed();
+                    sin.sin_family = libc::AF_INET as libc::sa_family_t;
+                    sin.sin_port = a.port().to_be();
+                    sin.sin_addr.s_addr = u32::from(*a.ip()).to_be();
+                    libc::bind(fd,
+                               &sin as *const _ as *const libc::sockaddr,
+                               mem::size_of_val(&sin) as libc::socklen_t)
+                }
+                SocketAddr::V6(ref a) => {
+                    let mut sin6: libc::sockaddr_in6 = mem::zeroed();
+                    sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+                    sin6.sin6_port = a.port().to_be();
+                    sin6.sin6_flowinfo = a.flowinfo();
+                    sin6.sin6_addr.s6_addr = a.ip().octets();
+                    sin6.sin6_scope_id = a.scope_id();
+                    libc::bind(fd,
+                               &sin6 as *const _ as *const libc::sockaddr,
+                               mem::size_of_val(&sin6) as libc::socklen_t)
+                }
+            };
+            if ret < 0 || libc::listen(fd, 1024) < 0 {
+                return Err(io::Error::last_os_error())
+            }
+            Ok(listener)
+        }
+    }
+
+    unsafe fn setsockopt(fd: RawFd, opt: libc::c_int, val: libc::c_int)
+                         -> io::Result<()> {
+        let ret = libc::setsockopt(fd,
+                                   libc::SOL_SOCKET,
+                                   opt,
+                                   &val as *const _ as *const libc::c_void,
+                                   mem::size_of_val(&val) as libc::socklen_t);
+        if ret < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
     impl AsRawFd for TcpStream {
         fn as_raw_fd(&self) -> RawFd {
             self.io.get_ref().as_raw_fd()
@@ -0,0 +1,956 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::net::Shutdown;
+use std::os::unix::net::{self, SocketAddr};
+use std::os::unix::prelude::*;
+use std::path::Path;
+use std::ptr;
+
+use futures::stream::Stream;
+use futures::{self, Future, Poll, Async};
+use libc;
+use mio;
+use mio::unix::EventedFd;
+
+use io::{Io, IoStream};
+use net::sys;
+use reactor::{Handle, PollEvented, ShutdownSignal};
+
+/// An I/O object representing a Unix domain socket listening for incoming
+/// connections.
+///
+/// This object can be converted into a stream of incoming connections for
+/// various forms of processing.
+pub struct UnixListener {
+    io: PollEvented<Socket<net::UnixListener>>,
+}
+
+/// Stream returned by the `UnixListener::incoming` function representing the
+/// stream of sockets received from a listener.
+pub struct UnixIncoming {
+    inner: IoStream<(UnixStream, SocketAddr)>,
+}
+
+/// An I/O object representing a Unix domain stream socket connected to a
+/// peer.
+pub struct UnixStream {
+    io: PollEvented<Socket<net::UnixStream>>,
+}
+
+/// Future returned by `UnixStream::connect` which will resolve to a
+/// `UnixStream` when the stream is connected.
+pub struct UnixStreamConnect {
+    inner: UnixStreamConnectState,
+}
+
+enum UnixStreamConnectState {
+    Waiting(UnixStream),
+    Error(io::Error),
+    Empty,
+}
+
+/// An I/O object representing a Unix domain datagram socket.
+pub struct UnixDatagram {
+    io: PollEvented<Socket<net::UnixDatagram>>,
+}
+
+/// Credentials of the process on the other end of a Unix domain socket, as
+/// returned by `UnixStream::peer_cred`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UCred {
+    /// The user ID of the peer process.
+    pub uid: libc::uid_t,
+    /// The group ID of the peer process.
+    pub gid: libc::gid_t,
+    /// The process ID of the peer process, if the platform reports it.
+    pub pid: Option<libc::pid_t>,
+}
+
+// A std socket wrapped up so it can be registered with an event loop
+struct Socket<T>(T);
+
+impl UnixListener {
+    /// Creates a new Unix domain socket listener bound to the specified path,
+    /// associated with this event loop.
+    pub fn bind<P: AsRef<Path>>(path: P, handle: &Handle)
+                                -> io::Result<UnixListener> {
+        let l = try!(net::UnixListener::bind(path));
+        UnixListener::from_listener(l, handle)
+    }
+
+    /// Creates a new Unix domain socket listener bound to `name` in the
+    /// abstract namespace, associated with this event loop.
+    ///
+    /// Abstract addresses don't exist on the filesystem and are removed
+    /// automatically once all sockets bound to them are closed.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn bind_abstract(name: &[u8], handle: &Handle)
+                         -> io::Result<UnixListener> {
+        let fd = try!(bind(&abstract_path(name), libc::SOCK_STREAM));
+        let l = unsafe { net::UnixListener::from_raw_fd(fd) };
+        if unsafe { libc::listen(fd, 128) } < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        UnixListener::from_listener(l, handle)
+    }
+
+    /// Creates a new Unix domain socket listener from the standard library's
+    /// listener, associating it with the event loop that `handle` refers to.
+    pub fn from_listener(listener: net::UnixListener, handle: &Handle)
+                         -> io::Result<UnixListener> {
+        try!(listener.set_nonblocking(true));
+        let io = try!(PollEvented::new(Socket(listener), handle));
+        Ok(UnixListener { io: io })
+    }
+
+    /// Test whether this socket is ready to be read or not.
+    pub fn poll_read(&self) -> Async<()> {
+        self.io.poll_read()
+    }
+
+    /// Returns the local address that this listener is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.io.get_ref().0.local_addr()
+    }
+
+    /// Returns the value of the `SO_ERROR` option.
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        self.io.get_ref().0.take_error()
+    }
+
+    /// Consumes this listener, returning a stream of the sockets this listener
+    /// accepts.
+    ///
+    /// This method returns an implementation of the `Stream` trait which
+    /// resolves to the sockets the are accepted on this listener. The stream
+    /// ends once shutdown of the event loop is requested through
+    /// `Remote::shutdown`.
+    pub fn incoming(self) -> UnixIncoming {
+        struct MyIncoming {
+            inner: UnixListener,
+            shutdown: ShutdownSignal,
+        }
+
+        impl Stream for MyIncoming {
+            type Item = (net::UnixStream, SocketAddr);
+            type Error = io::Error;
+
+            fn poll(&mut self) -> Poll<Option<Self::Item>, io::Error> {
+                // Stop accepting once the event loop is shutting down.
+                if let Ok(Async::Ready(())) = self.shutdown.poll() {
+                    return Ok(Async::Ready(None))
+                }
+                if let Async::NotReady = self.inner.io.poll_read() {
+                    return Ok(Async::NotReady)
+                }
+                match self.inner.io.get_ref().0.accept() {
+                    Ok(pair) => Ok(Async::Ready(Some(pair))),
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        self.inner.io.need_read();
+                        Ok(Async::NotReady)
+                    }
+                    Err(e) => Err(e)
+                }
+            }
+        }
+
+        let remote = self.io.remote().clone();
+        let stream = MyIncoming {
+            shutdown: remote.shutdown_signal(),
+            inner: self,
+        };
+        UnixIncoming {
+            inner: stream.and_then(move |(sock, addr)| {
+                // If the event loop is gone then the closure is dropped, and
+                // so is `tx`.
+                let (tx, rx) = futures::oneshot();
+                drop(remote.spawn(move |handle| {
+                    let res = UnixStream::from_stream(sock, handle).map(|s| {
+                        (s, addr)
+                    });
+                    tx.complete(res);
+                    Ok(())
+                }));
+                rx.then(|r| {
+                    r.unwrap_or_else(|_| {
+                        Err(io::Error::new(io::ErrorKind::Other, "event loop gone"))
+                    })
+                })
+            }).boxed(),
+        }
+    }
+}
+
+impl fmt::Debug for UnixListener {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.io.get_ref().0.fmt(f)
+    }
+}
+
+impl Stream for UnixIncoming {
+    type Item = (UnixStream, SocketAddr);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, io::Error> {
+        self.inner.poll()
+    }
+}
+
+impl UnixStream {
+    /// Connects to the Unix domain socket bound to the specified path.
+    ///
+    /// The returned future will be resolved once the stream has successfully
+    /// connected. If an error happens during the connection or during the
+    /// socket creation, that error will be returned to the future instead.
+    pub fn connect<P: AsRef<Path>>(path: P, handle: &Handle)
+                                   -> UnixStreamConnect {
+        let path = path.as_ref().as_os_str().as_bytes();
+        UnixStream::connect_bytes(path, handle)
+    }
+
+    /// Connects to the Unix domain socket bound to `name` in the abstract
+    /// namespace.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn connect_abstract(name: &[u8], handle: &Handle)
+                            -> UnixStreamConnect {
+        UnixStream::connect_bytes(&abstract_path(name), handle)
+    }
+
+    fn connect_bytes(path: &[u8], handle: &Handle) -> UnixStreamConnect {
+        let inner = match connect(path) {
+            Ok(sock) => {
+                match UnixStream::new(sock, handle) {
+                    Ok(s) => UnixStreamConnectState::Waiting(s),
+                    Err(e) => UnixStreamConnectState::Error(e),
+                }
+            }
+            Err(e) => UnixStreamConnectState::Error(e),
+        };
+        UnixStreamConnect { inner: inner }
+    }
+
+    /// Creates an unnamed pair of connected sockets, both associated with the
+    /// event loop that `handle` refers to.
+    pub fn pair(handle: &Handle) -> io::Result<(UnixStream, UnixStream)> {
+        let (a, b) = try!(net::UnixStream::pair());
+        let a = try!(UnixStream::from_stream(a, handle));
+        let b = try!(UnixStream::from_stream(b, handle));
+        Ok((a, b))
+    }
+
+    /// Creates a new `UnixStream` from the already connected socket provided,
+    /// associating it with the event loop that `handle` refers to.
+    pub fn from_stream(stream: net::UnixStream, handle: &Handle)
+                       -> io::Result<UnixStream> {
+        try!(stream.set_nonblocking(true));
+        UnixStream::new(stream, handle)
+    }
+
+    fn new(stream: net::UnixStream, handle: &Handle) -> io::Result<UnixStream> {
+        let io = try!(PollEvented::new(Socket(stream), handle));
+        Ok(UnixStream { io: io })
+    }
+
+    /// Test whether this socket is ready to be read or not.
+    ///
+    /// If the socket is *not* readable then the current task is scheduled to
+    /// get a notification when the socket does become readable. That is, this
+    /// is only suitable for calling in a `Future::poll` method and will
+    /// automatically handle ensuring a retry once the socket is readable again.
+    pub fn poll_read(&self) -> Async<()> {
+        self.io.poll_read()
+    }
+
+    /// Test whether this socket is ready to be written to or not.
+    ///
+    /// If the socket is *not* writable then the current task is scheduled to
+    /// get a notification when the socket does become writable. That is, this
+    /// is only suitable for calling in a `Future::poll` method and will
+    /// automatically handle ensuring a retry once the socket is writable again.
+    pub fn poll_write(&self) -> Async<()> {
+        self.io.poll_write()
+    }
+
+    /// Returns the local address that this stream is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.io.get_ref().0.local_addr()
+    }
+
+    /// Returns the remote address that this stream is connected to.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.io.get_ref().0.peer_addr()
+    }
+
+    /// Returns the credentials of the process on the other end of this
+    /// socket.
+    ///
+    /// On Linux this is the process which connected (or listened) at the time
+    /// the connection was established, as reported by `SO_PEERCRED`. On the
+    /// BSDs and macOS only the user and group are available.
+    #[cfg(any(target_os = "linux", target_os = "android",
+              target_os = "macos", target_os = "ios", target_os = "freebsd",
+              target_os = "openbsd", target_os = "netbsd",
+              target_os = "dragonfly"))]
+    pub fn peer_cred(&self) -> io::Result<UCred> {
+        peer_cred(self.as_raw_fd())
+    }
+
+    /// Returns the value of the `SO_ERROR` option.
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        self.io.get_ref().0.take_error()
+    }
+
+    /// Shuts down the read, write, or both halves of this connection.
+    ///
+    /// This function will cause all pending and future I/O on the specified
+    /// portions to return immediately with an appropriate value (see the
+    /// documentation of `Shutdown`).
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.io.get_ref().0.shutdown(how)
+    }
+
+    /// Sends `buf` on this socket along with the file descriptors in `fds`,
+    /// returning the number of bytes written.
+    ///
+    /// The descriptors are duplicated into the receiving process with
+    /// `SCM_RIGHTS` ancillary data and remain open in this process. At least
+    /// one byte of `buf` must be sent for the descriptors to be delivered.
+    ///
+    /// Like `write`, this returns a "would block" error if the socket isn't
+    /// writable, in which case the current task will be notified when it
+    /// becomes writable again.
+    pub fn send_fds(&self, buf: &[u8], fds: &[RawFd]) -> io::Result<usize> {
+        if let Async::NotReady = self.io.poll_write() {
+            return Err(mio::would_block())
+        }
+        let r = send_fds(self.as_raw_fd(), buf, fds);
+        if is_wouldblock(&r) {
+            self.io.need_write();
+        }
+        r
+    }
+
+    /// Receives data into `buf` along with any file descriptors sent with
+    /// it, which are stored into `fds`.
+    ///
+    /// On success returns the number of bytes read and the number of
+    /// descriptors stored. The caller owns the received descriptors and is
+    /// responsible for closing them. Received descriptors have close-on-exec
+    /// set.
+    ///
+    /// If more descriptors arrive than fit in `fds` the extra ones are lost,
+    /// so in that case every descriptor received with this message is closed
+    /// and an error is returned. The data read along with them is discarded
+    /// as well, so `fds` should have room for as many descriptors as the peer
+    /// may send at once.
+    ///
+    /// Like `read`, this returns a "would block" error if the socket isn't
+    /// readable, in which case the current task will be notified when it
+    /// becomes readable again.
+    pub fn recv_fds(&self, buf: &mut [u8], fds: &mut [RawFd])
+                    -> io::Result<(usize, usize)> {
+        if let Async::NotReady = self.io.poll_read() {
+            return Err(mio::would_block())
+        }
+        let r = recv_fds(self.as_raw_fd(), buf, fds);
+        if is_wouldblock(&r) {
+            self.io.need_read();
+        }
+        r
+    }
+}
+
+impl Read for UnixStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.io.read(buf)
+    }
+}
+
+impl Write for UnixStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.io.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.io.flush()
+    }
+}
+
+impl Io for UnixStream {
+    fn poll_read(&mut self) -> Async<()> {
+        <UnixStream>::poll_read(self)
+    }
+
+    fn poll_write(&mut self) -> Async<()> {
+        <UnixStream>::poll_write(self)
+    }
+}
+
+impl<'a> Read for &'a UnixStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&self.io).read(buf)
+    }
+}
+
+impl<'a> Write for &'a UnixStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&self.io).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&self.io).flush()
+    }
+}
+
+impl<'a> Io for &'a UnixStream {
+    fn poll_read(&mut self) -> Async<()> {
+        <UnixStream>::poll_read(self)
+    }
+
+    fn poll_write(&mut self) -> Async<()> {
+        <UnixStream>::poll_write(self)
+    }
+}
+
+impl fmt::Debug for UnixStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.io.get_ref().0.fmt(f)
+    }
+}
+
+impl Future for UnixStreamConnect {
+    type Item = UnixStream;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<UnixStream, io::Error> {
+        {
+            let stream = match self.inner {
+                UnixStreamConnectState::Waiting(ref s) => s,
+                UnixStreamConnectState::Error(_) => {
+                    match mem::replace(&mut self.inner,
+                                       UnixStreamConnectState::Empty) {
+                        UnixStreamConnectState::Error(e) => return Err(e),
+                        _ => panic!(),
+                    }
+                }
+                UnixStreamConnectState::Empty => {
+                    panic!("can't poll Unix stream twice")
+                }
+            };
+
+            // Once we're writable the connection has either completed or
+            // failed, and `SO_ERROR` tells us which.
+            if let Async::NotReady = stream.io.poll_write() {
+                return Ok(Async::NotReady)
+            }
+            if let Some(e) = try!(stream.take_error()) {
+                return Err(e)
+            }
+        }
+        match mem::replace(&mut self.inner, UnixStreamConnectState::Empty) {
+            UnixStreamConnectState::Waiting(stream) => Ok(Async::Ready(stream)),
+            _ => panic!(),
+        }
+    }
+}
+
+impl UnixDatagram {
+    /// Creates a new Unix datagram socket bound to the specified path.
+    pub fn bind<P: AsRef<Path>>(path: P, handle: &Handle)
+                                -> io::Result<UnixDatagram> {
+        let s = try!(net::UnixDatagram::bind(path));
+        UnixDatagram::from_datagram(s, handle)
+    }
+
+    /// Creates a new Unix datagram socket bound to `name` in the abstract
+    /// namespace.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn bind_abstract(name: &[u8], handle: &Handle)
+                         -> io::Result<UnixDatagram> {
+        let fd = try!(bind(&abstract_path(name), libc::SOCK_DGRAM));
+        let s = unsafe { net::UnixDatagram::from_raw_fd(fd) };
+        UnixDatagram::from_datagram(s, handle)
+    }
+
+    /// Creates a new Unix datagram socket which is not bound to any address.
+    pub fn unbound(handle: &Handle) -> io::Result<UnixDatagram> {
+        let s = try!(net::UnixDatagram::unbound());
+        UnixDatagram::from_datagram(s, handle)
+    }
+
+    /// Creates an unnamed pair of connected datagram sockets.
+    pub fn pair(handle: &Handle) -> io::Result<(UnixDatagram, UnixDatagram)> {
+        let (a, b) = try!(net::UnixDatagram::pair());
+        let a = try!(UnixDatagram::from_datagram(a, handle));
+        let b = try!(UnixDatagram::from_datagram(b, handle));
+        Ok((a, b))
+    }
+
+    /// Creates a new `UnixDatagram` from the standard library's socket,
+    /// associating it with the event loop that `handle` refers to.
+    pub fn from_datagram(socket: net::UnixDatagram, handle: &Handle)
+                         -> io::Result<UnixDatagram> {
+        try!(socket.set_nonblocking(true));
+        let io = try!(PollEvented::new(Socket(socket), handle));
+        Ok(UnixDatagram { io: io })
+    }
+
+    /// Connects the socket to the specified path, after which `send` and
+    /// `recv` can be used.
+    pub fn connect<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.io.get_ref().0.connect(path)
+    }
+
+    /// Test whether this socket is ready to be read or not.
+    pub fn poll_read(&self) -> Async<()> {
+        self.io.poll_read()
+    }
+
+    /// Test whether this socket is ready to be written to or not.
+    pub fn poll_write(&self) -> Async<()> {
+        self.io.poll_write()
+    }
+
+    /// Returns the local address that this socket is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.io.get_ref().0.local_addr()
+    }
+
+    /// Returns the address of this socket's peer, if it's connected.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.io.get_ref().0.peer_addr()
+    }
+
+    /// Sends data on the socket to the specified path. On success, returns
+    /// the number of bytes written.
+    pub fn send_to<P: AsRef<Path>>(&self, buf: &[u8], path: P)
+                                   -> io::Result<usize> {
+        if let Async::NotReady = self.io.poll_write() {
+            return Err(mio::would_block())
+        }
+        let r = self.io.get_ref().0.send_to(buf, path);
+        if is_wouldblock(&r) {
+            self.io.need_write();
+        }
+        r
+    }
+
+    /// Sends data on the socket to the socket bound to `name` in the abstract
+    /// namespace. On success, returns the number of bytes written.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn send_to_abstract(&self, buf: &[u8], name: &[u8])
+                            -> io::Result<usize> {
+        if let Async::NotReady = self.io.poll_write() {
+            return Err(mio::would_block())
+        }
+        let r = send_to(self.as_raw_fd(), buf, &abstract_path(name));
+        if is_wouldblock(&r) {
+            self.io.need_write();
+        }
+        r
+    }
+
+    /// Sends data on the socket to its connected peer.
+    pub fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        if let Async::NotReady = self.io.poll_write() {
+            return Err(mio::would_block())
+        }
+        let r = self.io.get_ref().0.send(buf);
+        if is_wouldblock(&r) {
+            self.io.need_write();
+        }
+        r
+    }
+
+    /// Receives data from the socket. On success, returns the number of bytes
+    /// read and the address from whence the data came.
+    pub fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        if let Async::NotReady = self.io.poll_read() {
+            return Err(mio::would_block())
+        }
+        let r = self.io.get_ref().0.recv_from(buf);
+        if is_wouldblock(&r) {
+            self.io.need_read();
+        }
+        r
+    }
+
+    /// Receives data from the socket's connected peer.
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Async::NotReady = self.io.poll_read() {
+            return Err(mio::would_block())
+        }
+        let r = self.io.get_ref().0.recv(buf);
+        if is_wouldblock(&r) {
+            self.io.need_read();
+        }
+        r
+    }
+
+    /// Sends a datagram to the connected peer along with the file
+    /// descriptors in `fds`.
+    ///
+    /// See `UnixStream::send_fds` for details.
+    pub fn send_fds(&self, buf: &[u8], fds: &[RawFd]) -> io::Result<usize> {
+        if let Async::NotReady = self.io.poll_write() {
+            return Err(mio::would_block())
+        }
+        let r = send_fds(self.as_raw_fd(), buf, fds);
+        if is_wouldblock(&r) {
+            self.io.need_write();
+        }
+        r
+    }
+
+    /// Receives a datagram along with any file descriptors sent with it.
+    ///
+    /// See `UnixStream::recv_fds` for details.
+    pub fn recv_fds(&self, buf: &mut [u8], fds: &mut [RawFd])
+                    -> io::Result<(usize, usize)> {
+        if let Async::NotReady = self.io.poll_read() {
+            return Err(mio::would_block())
+        }
+        let r = recv_fds(self.as_raw_fd(), buf, fds);
+        if is_wouldblock(&r) {
+            self.io.need_read();
+        }
+        r
+    }
+
+    /// Returns the value of the `SO_ERROR` option.
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        self.io.get_ref().0.take_error()
+    }
+
+    /// Shut down the read, write, or both halves of this connection.
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.io.get_ref().0.shutdown(how)
+    }
+}
+
+impl fmt::Debug for UnixDatagram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.io.get_ref().0.fmt(f)
+    }
+}
+
+impl AsRawFd for UnixListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.io.get_ref().0.as_raw_fd()
+    }
+}
+
+impl AsRawFd for UnixStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.io.get_ref().0.as_raw_fd()
+    }
+}
+
+impl AsRawFd for UnixDatagram {
+    fn as_raw_fd(&self) -> RawFd {
+        self.io.get_ref().0.as_raw_fd()
+    }
+}
+
+fn is_wouldblock<T>(r: &io::Result<T>) -> bool {
+    match *r {
+        Ok(_) => false,
+        Err(ref e) => e.kind() == io::ErrorKind::WouldBlock,
+    }
+}
+
+// Abstract names are distinguished from filesystem paths by a leading nul
+// byte in `sun_path`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn abstract_path(name: &[u8]) -> Vec<u8> {
+    let mut path = Vec::with_capacity(name.len() + 1);
+    path.push(0);
+    path.extend_from_slice(name);
+    path
+}
+
+// Builds the address for `path`, which is either a filesystem path or, if it
+// starts with a nul byte, an abstract name.
+fn sockaddr_un(path: &[u8]) -> io::Result<(libc::sockaddr_un, libc::socklen_t)> {
+    unsafe {
+        let mut addr: libc::sockaddr_un = mem::zeroed();
+        addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+        if path.len() >= addr.sun_path.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "path must be shorter than SUN_LEN"))
+        }
+        for (dst, src) in addr.sun_path.iter_mut().zip(path) {
+            *dst = *src as libc::c_char;
+        }
+        let base = &addr.sun_path as *const _ as usize -
+                   &addr as *const _ as usize;
+        let mut len = base + path.len();
+        // Filesystem paths are nul terminated, abstract names aren't
+        if path.get(0) != Some(&0) {
+            len += 1;
+        }
+        Ok((addr, len as libc::socklen_t))
+    }
+}
+
+// Creates a new nonblocking, close-on-exec Unix domain socket of type `ty`.
+fn socket(ty: libc::c_int) -> io::Result<RawFd> {
+    sys::socket(libc::AF_UNIX, ty, true)
+}
+
+// Creates a new socket of type `ty` bound to `path`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn bind(path: &[u8], ty: libc::c_int) -> io::Result<RawFd> {
+    let (addr, len) = try!(sockaddr_un(path));
+    let fd = try!(socket(ty));
+    let ret = unsafe {
+        libc::bind(fd, &addr as *const _ as *const libc::sockaddr, len)
+    };
+    if ret < 0 {
+        let err = io::Error::last_os_error();
+        unsafe {
+            libc::close(fd);
+        }
+        return Err(err)
+    }
+    Ok(fd)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn send_to(fd: RawFd, buf: &[u8], path: &[u8]) -> io::Result<usize> {
+    let (addr, len) = try!(sockaddr_un(path));
+    let n = unsafe {
+        libc::sendto(fd,
+                     buf.as_ptr() as *const libc::c_void,
+                     buf.len(),
+                     SEND_FLAGS,
+                     &addr as *const _ as *const libc::sockaddr,
+                     len)
+    };
+    if n < 0 {
+        return Err(io::Error::last_os_error())
+    }
+    Ok(n as usize)
+}
+
+// Issues a nonblocking `connect` to the address `path`, which is either a
+// filesystem path or, if it starts with a nul byte, an abstract name.
+fn connect(path: &[u8]) -> io::Result<net::UnixStream> {
+    let (addr, len) = try!(sockaddr_un(path));
+    unsafe {
+        let fd = try!(socket(libc::SOCK_STREAM));
+        let sock = net::UnixStream::from_raw_fd(fd);
+
+        let ret = libc::connect(fd,
+                                &addr as *const _ as *const libc::sockaddr,
+                                len);
+        if ret < 0 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(libc::EINPROGRESS) {
+                return Err(err)
+            }
+        }
+        Ok(sock)
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const SEND_FLAGS: libc::c_int = libc::MSG_NOSIGNAL;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+const SEND_FLAGS: libc::c_int = 0;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const RECV_FLAGS: libc::c_int = libc::MSG_CMSG_CLOEXEC;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+const RECV_FLAGS: libc::c_int = 0;
+
+// Allocates a suitably aligned buffer for a control message carrying `n`
+// file descriptors.
+fn cmsg_buffer(n: usize) -> Vec<u64> {
+    let bytes = (n * mem::size_of::<RawFd>()) as libc::c_uint;
+    let space = unsafe { libc::CMSG_SPACE(bytes) as usize };
+    vec![0; (space + 7) / 8]
+}
+
+fn send_fds(fd: RawFd, buf: &[u8], fds: &[RawFd]) -> io::Result<usize> {
+    unsafe {
+        let mut iov = libc::iovec {
+            iov_base: buf.as_ptr() as *mut libc::c_void,
+            iov_len: buf.len(),
+        };
+        let mut msg: libc::msghdr = mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+
+        let mut control = cmsg_buffer(fds.len());
+        if fds.len() > 0 {
+            let bytes = (fds.len() * mem::size_of::<RawFd>()) as libc::c_uint;
+            msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+            msg.msg_controllen = libc::CMSG_SPACE(bytes) as _;
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(bytes) as _;
+            ptr::copy_nonoverlapping(fds.as_ptr(),
+                                     libc::CMSG_DATA(cmsg) as *mut RawFd,
+                                     fds.len());
+        }
+
+        let n = libc::sendmsg(fd, &msg, SEND_FLAGS);
+        if n < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(n as usize)
+    }
+}
+
+fn recv_fds(fd: RawFd, buf: &mut [u8], fds: &mut [RawFd])
+            -> io::Result<(usize, usize)> {
+    unsafe {
+        let mut iov = libc::iovec {
+            iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+            iov_len: buf.len(),
+        };
+        let mut msg: libc::msghdr = mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+
+        let mut control = cmsg_buffer(fds.len());
+        if fds.len() > 0 {
+            let bytes = (fds.len() * mem::size_of::<RawFd>()) as libc::c_uint;
+            msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+            msg.msg_controllen = libc::CMSG_SPACE(bytes) as _;
+        }
+
+        let n = libc::recvmsg(fd, &mut msg, RECV_FLAGS);
+        if n < 0 {
+            return Err(io::Error::last_os_error())
+        }
+
+        // The kernel sets MSG_CTRUNC when descriptors didn't fit in the
+        // control buffer, in which case it has already closed them.
+        let mut truncated = msg.msg_flags & libc::MSG_CTRUNC != 0;
+        let mut count = 0;
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET &&
+               (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let data = libc::CMSG_DATA(cmsg);
+                let len = (*cmsg).cmsg_len as usize -
+                          (data as usize - cmsg as usize);
+                let data = data as *const RawFd;
+                for i in 0..len / mem::size_of::<RawFd>() {
+                    let received = ptr::read_unaligned(data.offset(i as isize));
+                    if count < fds.len() {
+                        if RECV_FLAGS == 0 {
+                            drop(sys::set_cloexec(received));
+                        }
+                        fds[count] = received;
+                        count += 1;
+                    } else {
+                        libc::close(received);
+                        truncated = true;
+                    }
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+        if truncated {
+            for &fd in fds[..count].iter() {
+                libc::close(fd);
+            }
+            return Err(io::Error::new(io::ErrorKind::Other,
+                                      "too many file descriptors received"))
+        }
+        Ok((n as usize, count))
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_cred(fd: RawFd) -> io::Result<UCred> {
+    unsafe {
+        let mut cred: libc::ucred = mem::zeroed();
+        let mut len = mem::size_of::<libc::ucred>() as libc::socklen_t;
+        let ret = libc::getsockopt(fd,
+                                   libc::SOL_SOCKET,
+                                   libc::SO_PEERCRED,
+                                   &mut cred as *mut _ as *mut libc::c_void,
+                                   &mut len);
+        if ret < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(UCred { uid: cred.uid, gid: cred.gid, pid: Some(cred.pid) })
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd",
+          target_os = "openbsd", target_os = "netbsd",
+          target_os = "dragonfly"))]
+fn peer_cred(fd: RawFd) -> io::Result<UCred> {
+    unsafe {
+        let mut uid = 0;
+        let mut gid = 0;
+        if libc::getpeereid(fd, &mut uid, &mut gid) < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(UCred { uid: uid, gid: gid, pid: None })
+    }
+}
+
+impl<T: Read> Read for Socket<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl<T: Write> Write for Socket<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<'a, T> Read for &'a Socket<T>
+    where &'a T: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&self.0).read(buf)
+    }
+}
+
+impl<'a, T> Write for &'a Socket<T>
+    where &'a T: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&self.0).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&self.0).flush()
+    }
+}
+
+impl<T: AsRawFd> mio::Evented for Socket<T> {
+    fn register(&self,
+                poll: &mio::Poll,
+                token: mio::Token,
+                interest: mio::Ready,
+                opts: mio::PollOpt) -> io::Result<()> {
+        EventedFd(&self.0.as_raw_fd()).register(poll, token, interest, opts)
+    }
+
+    fn reregister(&self,
+                  poll: &mio::Poll,
+                  token: mio::Token,
+                  interest: mio::Ready,
+                  opts: mio::PollOpt) -> io::Result<()> {
+        EventedFd(&self.0.as_raw_fd()).reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &mio::Poll) -> io::Result<()> {
+        EventedFd(&self.0.as_raw_fd()).deregister(poll)
+    }
+}
@@ -0,0 +1,267 @@
+//! Asynchronous child process management for Unix.
+//!
+//! This module contains the `CommandExt` trait which extends the standard
+//! library's `Command` type with the ability to spawn a child process whose
+//! exit status is a future and whose standard I/O pipes are registered with an
+//! event loop.
+//!
+//! Child processes are reaped by listening for `SIGCHLD` through the
+//! `reactor::signal` module, with one listener shared by all children spawned
+//! onto the same event loop. Whenever that signal is delivered each `Child`
+//! future checks (without blocking) whether its own process has exited.
+//!
+//! Note that, like the standard library, dropping a `Child` does not kill the
+//! process, nor does it wait for it to exit. If the future is dropped before
+//! the child has exited then the child will not be reaped.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::os::unix::prelude::*;
+use std::process::{self, Command, ExitStatus};
+use std::rc::{Rc, Weak};
+
+use futures::{Future, Poll, Async};
+use futures::stream::Stream;
+use futures::task::{self, Task};
+use libc;
+
+use reactor::{Handle, PollEvented, RawFdSource};
+use reactor::signal::{Signal, SIGCHLD};
+
+/// Extensions to the standard library's `Command` type to spawn processes
+/// onto an event loop.
+pub trait CommandExt {
+    /// Executes the command as a child process, returning a future for its
+    /// exit status.
+    ///
+    /// Any of stdin, stdout or stderr configured with `Stdio::piped()` are
+    /// placed into nonblocking mode and registered with the event loop that
+    /// `handle` is associated with. They can be taken out of the returned
+    /// `Child` with its `stdin`, `stdout` and `stderr` methods, and used with
+    /// the combinators in the `io` module like any other I/O object.
+    fn spawn_async(&mut self, handle: &Handle) -> io::Result<Child>;
+}
+
+/// A future representing a spawned child process, resolving to its exit
+/// status.
+///
+/// This type is created by the `CommandExt::spawn_async` method.
+pub struct Child {
+    child: process::Child,
+    reaper: Rc<Reaper>,
+    generation: usize,
+    stdin: Option<ChildStdin>,
+    stdout: Option<ChildStdout>,
+    stderr: Option<ChildStderr>,
+}
+
+/// The standard input of a child process, registered with an event loop.
+pub type ChildStdin = PollEvented<RawFdSource>;
+
+/// The standard output of a child process, registered with an event loop.
+pub type ChildStdout = PollEvented<RawFdSource>;
+
+/// The standard error of a child process, registered with an event loop.
+pub type ChildStderr = PollEvented<RawFdSource>;
+
+// Listens for SIGCHLD on behalf of every `Child` spawned onto one event loop.
+//
+// The `Signal` stream only remembers the last task which polled it, so
+// whichever child sees a SIGCHLD bumps `generation` and wakes up all the
+// others so they can check on their own process.
+struct Reaper {
+    sigchld: RefCell<Signal>,
+    generation: Cell<usize>,
+    // Tasks waiting for the next SIGCHLD, keyed by process id
+    waiters: RefCell<HashMap<u32, Task>>,
+}
+
+thread_local!(static REAPERS: RefCell<HashMap<usize, Weak<Reaper>>> =
+    RefCell::new(HashMap::new()));
+
+impl CommandExt for Command {
+    fn spawn_async(&mut self, handle: &Handle) -> io::Result<Child> {
+        // Start listening for SIGCHLD before spawning so we can't miss the
+        // notification for a child which exits immediately.
+        let reaper = try!(Reaper::get(handle));
+        let generation = reaper.generation.get();
+        let mut child = try!(self.spawn());
+        let (stdin, stdout, stderr) = match pipes(&mut child, handle) {
+            Ok(pipes) => pipes,
+            Err(e) => {
+                // Nobody is going to wait on the child now, so make sure it
+                // doesn't stick around as a zombie.
+                drop(child.kill());
+                drop(child.wait());
+                return Err(e)
+            }
+        };
+        Ok(Child {
+            child: child,
+            reaper: reaper,
+            generation: generation,
+            stdin: stdin,
+            stdout: stdout,
+            stderr: stderr,
+        })
+    }
+}
+
+fn pipes(child: &mut process::Child, handle: &Handle)
+         -> io::Result<(Option<ChildStdin>,
+                        Option<ChildStdout>,
+                        Option<ChildStderr>)> {
+    let stdin = match child.stdin.take() {
+        Some(io) => Some(try!(pipe(io, handle))),
+        None => None,
+    };
+    let stdout = match child.stdout.take() {
+        Some(io) => Some(try!(pipe(io, handle))),
+        None => None,
+    };
+    let stderr = match child.stderr.take() {
+        Some(io) => Some(try!(pipe(io, handle))),
+        None => None,
+    };
+    Ok((stdin, stdout, stderr))
+}
+
+fn pipe<T: IntoRawFd>(io: T, handle: &Handle)
+                      -> io::Result<PollEvented<RawFdSource>> {
+    let source = unsafe { RawFdSource::from_raw_fd(io.into_raw_fd()) };
+    let fd = source.as_raw_fd();
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+        if flags < 0 ||
+           libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) < 0 {
+            return Err(io::Error::last_os_error())
+        }
+    }
+    PollEvented::new(source, handle)
+}
+
+impl Reaper {
+    // Returns the reaper for the event loop `handle` refers to, creating it if
+    // no children are currently spawned onto that event loop.
+    fn get(handle: &Handle) -> io::Result<Rc<Reaper>> {
+        REAPERS.with(|reapers| {
+            let mut reapers = reapers.borrow_mut();
+            if let Some(reaper) = reapers.get(&handle.id()).and_then(|r| r.upgrade()) {
+                return Ok(reaper)
+            }
+            let reaper = Rc::new(Reaper {
+                sigchld: RefCell::new(try!(Signal::new(SIGCHLD, handle))),
+                generation: Cell::new(0),
+                waiters: RefCell::new(HashMap::new()),
+            });
+            reapers.retain(|_, r| r.upgrade().is_some());
+            reapers.insert(handle.id(), Rc::downgrade(&reaper));
+            Ok(reaper)
+        })
+    }
+
+    // Checks whether a SIGCHLD has been received since `generation`, updating
+    // it if so. Otherwise the current task is woken up on the next one.
+    fn poll(&self, pid: u32, generation: &mut usize) -> io::Result<bool> {
+        self.waiters.borrow_mut().insert(pid, task::park());
+        loop {
+            match try!(self.sigchld.borrow_mut().poll()) {
+                Async::Ready(Some(_)) => {}
+                Async::Ready(None) => {
+                    return Err(io::Error::new(io::ErrorKind::Other,
+                                              "SIGCHLD stream ended"))
+                }
+                Async::NotReady => break,
+            }
+            self.generation.set(self.generation.get() + 1);
+            for task in self.waiters.borrow().values() {
+                task.unpark();
+            }
+        }
+        let current = self.generation.get();
+        if current == *generation {
+            Ok(false)
+        } else {
+            *generation = current;
+            Ok(true)
+        }
+    }
+
+    // Stops waiting on behalf of `pid`. As its task may be the one the signal
+    // stream would have woken up, the remaining waiters are woken so one of
+    // them takes over.
+    fn release(&self, pid: u32) {
+        let mut waiters = self.waiters.borrow_mut();
+        if waiters.remove(&pid).is_some() {
+            for task in waiters.values() {
+                task.unpark();
+            }
+        }
+    }
+}
+
+impl Child {
+    /// Returns the OS-assigned process identifier associated with this child.
+    pub fn id(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// Forces the child to exit, sending it `SIGKILL`.
+    ///
+    /// The exit status can still be retrieved by waiting on this future.
+    pub fn kill(&mut self) -> io::Result<()> {
+        self.child.kill()
+    }
+
+    /// Takes the handle to the child's standard input, if it was piped and
+    /// hasn't already been taken.
+    pub fn stdin(&mut self) -> Option<ChildStdin> {
+        self.stdin.take()
+    }
+
+    /// Takes the handle to the child's standard output, if it was piped and
+    /// hasn't already been taken.
+    pub fn stdout(&mut self) -> Option<ChildStdout> {
+        self.stdout.take()
+    }
+
+    /// Takes the handle to the child's standard error, if it was piped and
+    /// hasn't already been taken.
+    pub fn stderr(&mut self) -> Option<ChildStderr> {
+        self.stderr.take()
+    }
+}
+
+impl Future for Child {
+    type Item = ExitStatus;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<ExitStatus, io::Error> {
+        loop {
+            if let Some(status) = try!(self.child.try_wait()) {
+                self.reaper.release(self.child.id());
+                return Ok(Async::Ready(status))
+            }
+            // If a SIGCHLD arrived since we last checked, then some child
+            // exited so loop around and check whether it was ours.
+            let pid = self.child.id();
+            if !try!(self.reaper.poll(pid, &mut self.generation)) {
+                return Ok(Async::NotReady)
+            }
+        }
+    }
+}
+
+impl Drop for Child {
+    fn drop(&mut self) {
+        self.reaper.release(self.child.id());
+    }
+}
+
+impl fmt::Debug for Child {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Child").field("id", &self.child.id()).finish()
+    }
+}
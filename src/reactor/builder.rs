@@ -0,0 +1,322 @@
+//! Configuration of new event loops.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use mio;
+use slab::Slab;
+
+use reactor::{Core, Inner, MySetReadiness, NEXT_LOOP_ID};
+use reactor::shutdown;
+use reactor::{TOKEN_MESSAGES, TOKEN_FUTURE, TOKEN_READY};
+use reactor::channel::channel;
+use reactor::ready_queue::ReadyQueue;
+use wheel::Wheel;
+
+const SLAB_CAPACITY: usize = 1024 * 64;
+const EVENTS_CAPACITY: usize = 1024;
+const TIMER_RESOLUTION_MS: u64 = 1;
+const SHUTDOWN_GRACE_SECS: u64 = 30;
+
+/// A builder used to configure and create a new event loop.
+///
+/// `Core::new` is a shorthand for `Builder::new().build()`, and the defaults
+/// here match the configuration used by it.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use tokio_core::reactor::Builder;
+///
+/// let core = Builder::new()
+///     .slab_capacity(128)
+///     .max_tasks(10_000)
+///     .events_capacity(64)
+///     .timer_resolution(Duration::from_millis(10))
+///     .name("worker-1")
+///     .build()
+///     .unwrap();
+/// assert_eq!(core.name(), Some("worker-1"));
+/// ```
+pub struct Builder {
+    io_capacity: usize,
+    task_capacity: usize,
+    timeout_capacity: usize,
+    max_io_sources: usize,
+    max_tasks: usize,
+    max_timeouts: usize,
+    events_capacity: usize,
+    timer_resolution: Duration,
+    max_tasks_per_turn: usize,
+    max_io_per_turn: usize,
+    max_messages_per_turn: usize,
+    panic_hook: Option<Box<FnMut(usize, Box<Any + Send>)>>,
+    shutdown_grace: Duration,
+    name: Option<String>,
+}
+
+impl Builder {
+    /// Creates a new builder with the default configuration.
+    pub fn new() -> Builder {
+        Builder {
+            io_capacity: SLAB_CAPACITY,
+            task_capacity: SLAB_CAPACITY,
+            timeout_capacity: SLAB_CAPACITY,
+            max_io_sources: ::std::usize::MAX,
+            max_tasks: ::std::usize::MAX,
+            max_timeouts: ::std::usize::MAX,
+            events_capacity: EVENTS_CAPACITY,
+            timer_resolution: Duration::from_millis(TIMER_RESOLUTION_MS),
+            max_tasks_per_turn: ::std::usize::MAX,
+            max_io_per_turn: ::std::usize::MAX,
+            max_messages_per_turn: ::std::usize::MAX,
+            panic_hook: None,
+            shutdown_grace: Duration::from_secs(SHUTDOWN_GRACE_SECS),
+            name: None,
+        }
+    }
+
+    /// Sets the initial capacity of all of the event loop's internal tables,
+    /// for I/O sources, spawned tasks and timeouts.
+    ///
+    /// The tables grow as needed, so this only determines how much memory is
+    /// allocated up front. Defaults to 65536 entries each.
+    pub fn slab_capacity(&mut self, capacity: usize) -> &mut Builder {
+        self.io_capacity = capacity;
+        self.task_capacity = capacity;
+        self.timeout_capacity = capacity;
+        self
+    }
+
+    /// Sets the initial capacity of the table of I/O sources.
+    pub fn io_capacity(&mut self, capacity: usize) -> &mut Builder {
+        self.io_capacity = capacity;
+        self
+    }
+
+    /// Sets the initial capacity of the table of spawned tasks.
+    pub fn task_capacity(&mut self, capacity: usize) -> &mut Builder {
+        self.task_capacity = capacity;
+        self
+    }
+
+    /// Sets the initial capacity of the table of timeouts.
+    pub fn timeout_capacity(&mut self, capacity: usize) -> &mut Builder {
+        self.timeout_capacity = capacity;
+        self
+    }
+
+    /// Sets the maximum number of I/O sources which can be registered with
+    /// the event loop at once.
+    ///
+    /// Once the limit is reached creating further I/O objects fails with an
+    /// error. There is no limit by default.
+    pub fn max_io_sources(&mut self, max: usize) -> &mut Builder {
+        self.max_io_sources = max;
+        self
+    }
+
+    /// Sets the maximum number of tasks which can be spawned onto the event
+    /// loop at once.
+    ///
+    /// Once the limit is reached further tasks are dropped without being run
+    /// and an error is logged. `Handle::try_spawn` returns an error instead,
+    /// and a `JoinHandle` for such a task resolves to `JoinError::Dropped`.
+    /// There is no limit by default.
+    pub fn max_tasks(&mut self, max: usize) -> &mut Builder {
+        self.max_tasks = max;
+        self
+    }
+
+    /// Sets the maximum number of timeouts which can be active on the event
+    /// loop at once.
+    ///
+    /// Once the limit is reached creating further timeouts fails with an
+    /// error. There is no limit by default.
+    pub fn max_timeouts(&mut self, max: usize) -> &mut Builder {
+        self.max_timeouts = max;
+        self
+    }
+
+    /// Sets the maximum number of events received from the poller in a
+    /// single turn of the event loop. Defaults to 1024.
+    ///
+    /// # Panics
+    ///
+    /// The event loop panics on creation if this is zero.
+    pub fn events_capacity(&mut self, capacity: usize) -> &mut Builder {
+        self.events_capacity = capacity;
+        self
+    }
+
+    /// Sets the granularity of the event loop's timer. Defaults to one
+    /// millisecond.
+    ///
+    /// Timeouts are rounded up to a multiple of this, so a coarser resolution
+    /// trades precision for fewer wakeups.
+    ///
+    /// # Panics
+    ///
+    /// The event loop panics on creation if this is zero.
+    pub fn timer_resolution(&mut self, resolution: Duration) -> &mut Builder {
+        self.timer_resolution = resolution;
+        self
+    }
+
+    /// Limits the number of spawned tasks polled in a single turn.
+    ///
+    /// See `Core::set_max_tasks_per_turn` for more details. `build` fails if
+    /// this is zero.
+    pub fn max_tasks_per_turn(&mut self, max: usize) -> &mut Builder {
+        self.max_tasks_per_turn = max;
+        self
+    }
+
+    /// Limits the number of I/O events dispatched in a single turn.
+    ///
+    /// See `Core::set_max_io_per_turn` for more details. `build` fails if
+    /// this is zero.
+    pub fn max_io_per_turn(&mut self, max: usize) -> &mut Builder {
+        self.max_io_per_turn = max;
+        self
+    }
+
+    /// Limits the number of messages from `Remote` handles processed in a
+    /// single turn.
+    ///
+    /// See `Core::set_max_messages_per_turn` for more details. `build` fails
+    /// if this is zero.
+    pub fn max_messages_per_turn(&mut self, max: usize) -> &mut Builder {
+        self.max_messages_per_turn = max;
+        self
+    }
+
+    /// Installs a hook which isolates panics in spawned tasks.
+    ///
+    /// See `Core::set_panic_hook` for more details.
+    pub fn panic_hook<F>(&mut self, hook: F) -> &mut Builder
+        where F: FnMut(usize, Box<Any + Send>) + 'static,
+    {
+        self.panic_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Sets how long `Core::run_until_shutdown` gives tasks to complete once
+    /// shutdown has been requested. Defaults to 30 seconds.
+    pub fn shutdown_grace_period(&mut self, grace: Duration) -> &mut Builder {
+        self.shutdown_grace = grace;
+        self
+    }
+
+    /// Sets a name for the event loop, available afterwards through
+    /// `Core::name`.
+    ///
+    /// This is purely informational, typically the name of the thread the
+    /// event loop will run on, and is useful to tell event loops apart.
+    pub fn name<S: Into<String>>(&mut self, name: S) -> &mut Builder {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Creates a new event loop with this configuration.
+    ///
+    /// The panic hook, if any, is moved into the new event loop, so it won't
+    /// be installed in event loops created by later calls.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the per-turn limits is zero, along with any
+    /// error which happened while creating the underlying poller.
+    pub fn build(&mut self) -> io::Result<Core> {
+        assert!(self.events_capacity > 0, "events capacity must be nonzero");
+        if self.max_tasks_per_turn == 0 ||
+           self.max_io_per_turn == 0 ||
+           self.max_messages_per_turn == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "per-turn limits must be nonzero"))
+        }
+        let (tx, rx) = channel();
+        let io = try!(mio::Poll::new());
+        try!(io.register(&rx,
+                         TOKEN_MESSAGES,
+                         mio::Ready::readable(),
+                         mio::PollOpt::edge()));
+        let future_pair = mio::Registration::new(&io,
+                                                 TOKEN_FUTURE,
+                                                 mio::Ready::readable(),
+                                                 mio::PollOpt::level());
+        let ready_pair = mio::Registration::new(&io,
+                                                TOKEN_READY,
+                                                mio::Ready::readable(),
+                                                mio::PollOpt::level());
+        let ready = Arc::new(ReadyQueue::new(ready_pair.1));
+        let mut core = Core {
+            events: mio::Events::with_capacity(self.events_capacity),
+            tx: tx,
+            rx: rx,
+            _future_registration: future_pair.0,
+            future_readiness: Arc::new(MySetReadiness::new(future_pair.1)),
+            _ready_registration: ready_pair.0,
+            ready: ready.clone(),
+            ready_tasks: Vec::new(),
+            max_tasks: ::std::usize::MAX,
+            max_io: ::std::usize::MAX,
+            max_messages: ::std::usize::MAX,
+            deferred_io: VecDeque::new(),
+            messages_pending: false,
+            panic_hook: self.panic_hook.take(),
+            shutdown: Arc::new(shutdown::State::new()),
+            shutdown_grace: self.shutdown_grace,
+            name: self.name.clone(),
+
+            inner: Rc::new(RefCell::new(Inner {
+                id: NEXT_LOOP_ID.fetch_add(1, Ordering::Relaxed),
+                io: io,
+                io_dispatch: Slab::with_capacity(self.io_capacity),
+                task_dispatch: Slab::with_capacity(self.task_capacity),
+                ready: ready,
+                timeouts: Slab::with_capacity(self.timeout_capacity),
+                timer_wheel: Wheel::new(self.timer_resolution),
+                max_io_sources: self.max_io_sources,
+                max_tasks: self.max_tasks,
+                max_timeouts: self.max_timeouts,
+            })),
+        };
+        core.set_max_tasks_per_turn(self.max_tasks_per_turn);
+        core.set_max_io_per_turn(self.max_io_per_turn);
+        core.set_max_messages_per_turn(self.max_messages_per_turn);
+        Ok(core)
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Builder {
+        Builder::new()
+    }
+}
+
+impl fmt::Debug for Builder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Builder")
+         .field("io_capacity", &self.io_capacity)
+         .field("task_capacity", &self.task_capacity)
+         .field("timeout_capacity", &self.timeout_capacity)
+         .field("max_io_sources", &self.max_io_sources)
+         .field("max_tasks", &self.max_tasks)
+         .field("max_timeouts", &self.max_timeouts)
+         .field("events_capacity", &self.events_capacity)
+         .field("timer_resolution", &self.timer_resolution)
+         .field("panic_hook", &self.panic_hook.is_some())
+         .field("shutdown_grace", &self.shutdown_grace)
+         .field("name", &self.name)
+         .finish()
+    }
+}
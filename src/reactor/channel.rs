@@ -1,52 +1,105 @@
-//! A thin wrapper around a mpsc queue and mio-based channel information
+//! A thin wrapper around mpsc queues and a mio registration
 //!
 //! Normally the standard library's channels would suffice but we unfortunately
-//! need the `Sender<T>` half to be `Sync`, so to accomplish this for now we
-//! just vendor the same mpsc queue as the one in the standard library and then
-//! we pair that with the `mio::channel` module's Ctl pairs to control the
-//! readiness notifications on the channel.
+//! need the `Sender<T>` half to be `Sync`, so to accomplish this we use our own
+//! queues and pair them with a `mio::Registration` to control the readiness
+//! notifications on the channel.
+//!
+//! Messages normally go through a fixed-size ring, which doesn't allocate per
+//! message. If the ring fills up, for example because the event loop is busy,
+//! messages spill over onto an unbounded linked list until the event loop has
+//! caught up. Like the queue of ready tasks, the poller is only notified once
+//! per burst of messages rather than once per message.
 
 use std::cell::Cell;
 use std::io;
 use std::marker;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 use mio;
-use mio::channel::{ctl_pair, SenderCtl, ReceiverCtl};
 
-use mpsc_queue::{Queue, PopResult};
+use mpsc_queue::{self, PopResult};
+use ring_queue;
+
+// Number of messages which fit in the ring before spilling over.
+const RING_CAPACITY: usize = 1024;
 
 pub struct Sender<T> {
-    ctl: SenderCtl,
-    inner: Arc<Queue<T>>,
+    inner: Arc<Inner<T>>,
 }
 
 pub struct Receiver<T> {
-    ctl: ReceiverCtl,
-    inner: Arc<Queue<T>>,
+    registration: mio::Registration,
+    inner: Arc<Inner<T>>,
     _marker: marker::PhantomData<Cell<()>>, // this type is not Sync
 }
 
+struct Inner<T> {
+    ring: ring_queue::Queue<T>,
+    overflow: mpsc_queue::Queue<T>,
+
+    // Number of messages pushed onto `overflow` which haven't been popped yet.
+    // While this is nonzero senders push onto `overflow` even if there's space
+    // in the ring again, so that messages from each sender stay in order.
+    overflowed: AtomicUsize,
+
+    readiness: mio::SetReadiness,
+
+    // Whether the poller has been notified of new messages but the receiver
+    // hasn't yet seen that notification.
+    notified: AtomicBool,
+
+    // Set once the receiver has been dropped. After that point nobody is
+    // going to receive messages any more, so whoever pushes one is responsible
+    // for popping it back off and dropping it, holding `drain` while doing so
+    // as the queues only support one consumer at a time.
+    closed: AtomicBool,
+    drain: Mutex<()>,
+}
+
 pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
-    let inner = Arc::new(Queue::new());
-    let (tx, rx) = ctl_pair();
+    let (registration, readiness) = mio::Registration::new2();
+    let inner = Arc::new(Inner {
+        ring: ring_queue::Queue::new(RING_CAPACITY),
+        overflow: mpsc_queue::Queue::new(),
+        overflowed: AtomicUsize::new(0),
+        readiness: readiness,
+        notified: AtomicBool::new(false),
+        closed: AtomicBool::new(false),
+        drain: Mutex::new(()),
+    });
 
     let tx = Sender {
-        ctl: tx,
         inner: inner.clone(),
     };
     let rx = Receiver {
-        ctl: rx,
-        inner: inner.clone(),
+        registration: registration,
+        inner: inner,
         _marker: marker::PhantomData,
     };
     (tx, rx)
 }
 
 impl<T> Sender<T> {
+    /// Sends `data` to the receiver.
+    ///
+    /// If the receiver has been dropped then `data` is dropped instead and an
+    /// error is returned.
     pub fn send(&self, data: T) -> io::Result<()> {
         self.inner.push(data);
-        self.ctl.inc()
+        if self.inner.closed.load(Ordering::SeqCst) {
+            self.inner.drain();
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe,
+                                      "event loop is gone"))
+        }
+
+        // Only the first message since the receiver last caught up needs to
+        // wake up the poller.
+        if !self.inner.notified.swap(true, Ordering::SeqCst) {
+            try!(self.inner.readiness.set_readiness(mio::Ready::readable()));
+        }
+        Ok(())
     }
 }
 
@@ -58,41 +111,108 @@ impl<T> Receiver<T> {
         // We, however, are the only thread with a `Receiver<T>` because this
         // type is not `Sync`. and we never handed out another instance.
         match unsafe { self.inner.pop() } {
+            PopResult::Data(t) => return Ok(Some(t)),
+
+            // If a queue is in an inconsistent state then there's data in it
+            // but a sender hasn't finished its operation yet. The standard
+            // library performs a yield loop in that case, but we don't need
+            // to busy wait: that sender has yet to notify the poller, so once
+            // we've acknowledged the current notification below it'll wake
+            // us up again and we'll retry then.
+            PopResult::Empty |
+            PopResult::Inconsistent => {}
+        }
+
+        // We've caught up, so acknowledge the notification for the messages
+        // we've received, after which senders notify the poller again.
+        if !self.inner.notified.load(Ordering::SeqCst) {
+            return Ok(None)
+        }
+        // Reset readiness first, so a sender which notifies us after we've
+        // reset `notified` sets it again. Swapping rather than storing also
+        // synchronizes with any push made before that sender notified us, so
+        // we check the queues once more to see it.
+        try!(self.inner.readiness.set_readiness(mio::Ready::none()));
+        self.inner.notified.swap(false, Ordering::SeqCst);
+        match unsafe { self.inner.pop() } {
+            PopResult::Data(t) => Ok(Some(t)),
+            PopResult::Empty |
+            PopResult::Inconsistent => Ok(None),
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.inner.closed.store(true, Ordering::SeqCst);
+        self.inner.drain();
+    }
+}
+
+impl<T> Inner<T> {
+    fn push(&self, data: T) {
+        let data = if self.overflowed.load(Ordering::SeqCst) == 0 {
+            match self.ring.push(data) {
+                Ok(()) => return,
+                Err(data) => data,
+            }
+        } else {
+            data
+        };
+        self.overflowed.fetch_add(1, Ordering::SeqCst);
+        self.overflow.push(data);
+    }
+
+    // Everything in the ring was pushed before anything in the overflow list
+    // by the same sender, so the ring is always emptied first.
+    //
+    // This is unsafe for the same reason as the queues' `pop` methods.
+    unsafe fn pop(&self) -> PopResult<T> {
+        match self.ring.pop() {
+            PopResult::Empty => {}
+            other => return other,
+        }
+        if self.overflowed.load(Ordering::SeqCst) == 0 {
+            return PopResult::Empty
+        }
+        match self.overflow.pop() {
             PopResult::Data(t) => {
-                try!(self.ctl.dec());
-                Ok(Some(t))
+                self.overflowed.fetch_sub(1, Ordering::SeqCst);
+                PopResult::Data(t)
             }
 
-            // If the queue is either in an inconsistent or empty state, then
-            // we return `None` for both instances. Note that the standard
-            // library performs a yield loop in the event of `Inconsistent`,
-            // which means that there's data in the queue but a sender hasn't
-            // finished their operation yet.
-            //
-            // We do this because the queue will continue to be readable as
-            // the thread performing the push will eventually call `inc`, so
-            // if we return `None` and the event loop just loops aruond calling
-            // this method then we'll eventually get back to the same spot
-            // and due the retry.
-            //
-            // Basically, the inconsistent state doesn't mean we need to busy
-            // wait, but instead we can forge ahead and assume by the time we
-            // go to the kernel and come back we'll no longer be in an
-            // inconsistent state.
+            // The sender which bumped `overflowed` is still pushing.
             PopResult::Empty |
-            PopResult::Inconsistent => Ok(None),
+            PopResult::Inconsistent => PopResult::Inconsistent,
+        }
+    }
+
+    fn drain(&self) {
+        // Messages are dropped outside the lock as dropping one may well send
+        // another.
+        let mut dropped = Vec::new();
+        {
+            let _lock = self.drain.lock().unwrap();
+            loop {
+                match unsafe { self.pop() } {
+                    PopResult::Data(t) => dropped.push(t),
+                    PopResult::Empty |
+                    PopResult::Inconsistent => break,
+                }
+            }
         }
+        drop(dropped);
     }
 }
 
-// Just delegate everything to `self.ctl`
+// Just delegate everything to `self.registration`
 impl<T> mio::Evented for Receiver<T> {
     fn register(&self,
                 poll: &mio::Poll,
                 token: mio::Token,
                 interest: mio::Ready,
                 opts: mio::PollOpt) -> io::Result<()> {
-        self.ctl.register(poll, token, interest, opts)
+        self.registration.register(poll, token, interest, opts)
     }
 
     fn reregister(&self,
@@ -100,18 +220,17 @@ impl<T> mio::Evented for Receiver<T> {
                   token: mio::Token,
                   interest: mio::Ready,
                   opts: mio::PollOpt) -> io::Result<()> {
-        self.ctl.reregister(poll, token, interest, opts)
+        self.registration.reregister(poll, token, interest, opts)
     }
 
     fn deregister(&self, poll: &mio::Poll) -> io::Result<()> {
-        self.ctl.deregister(poll)
+        self.registration.deregister(poll)
     }
 }
 
 impl<T> Clone for Sender<T> {
     fn clone(&self) -> Sender<T> {
         Sender {
-            ctl: self.ctl.clone(),
             inner: self.inner.clone(),
         }
     }
@@ -0,0 +1,166 @@
+//! Combinators for bounding how long futures and streams may take.
+//!
+//! This module contains the `FutureTimeoutExt` and `StreamTimeoutExt`
+//! extension traits which can be used to fail a future or stream with an error
+//! of kind `io::ErrorKind::TimedOut` if it doesn't make progress in time.
+
+use std::io;
+use std::time::{Duration, Instant};
+
+use futures::{Future, Poll, Async};
+use futures::stream::Stream;
+
+use reactor::{Handle, Timeout};
+
+/// An extension trait for futures which provides timeout combinators backed
+/// by an event loop's timer.
+///
+/// This trait is implemented for all futures whose error type can be created
+/// from an `io::Error`.
+pub trait FutureTimeoutExt: Future + Sized {
+    /// Fails this future with a `TimedOut` error if it doesn't resolve within
+    /// `dur` time from now.
+    ///
+    /// The timeout is registered with the event loop that `handle` is
+    /// associated with, and is cancelled as soon as this future completes.
+    fn timeout(self, dur: Duration, handle: &Handle) -> Deadline<Self> {
+        self.deadline(Instant::now() + dur, handle)
+    }
+
+    /// Fails this future with a `TimedOut` error if it doesn't resolve before
+    /// the instant `at`.
+    fn deadline(self, at: Instant, handle: &Handle) -> Deadline<Self> {
+        Deadline {
+            future: self,
+            timeout: Some(Timeout::new_at(at, handle)),
+        }
+    }
+}
+
+impl<F> FutureTimeoutExt for F
+    where F: Future,
+          F::Error: From<io::Error>,
+{
+}
+
+/// An extension trait for streams which provides timeout combinators backed
+/// by an event loop's timer.
+///
+/// This trait is implemented for all streams whose error type can be created
+/// from an `io::Error`.
+pub trait StreamTimeoutExt: Stream + Sized {
+    /// Fails this stream with a `TimedOut` error whenever the next item takes
+    /// longer than `dur` to arrive.
+    ///
+    /// The deadline is pushed forward every time the stream yields an item,
+    /// reusing a single timeout on the event loop for the whole lifetime of
+    /// the stream. After a `TimedOut` error the deadline is restarted so the
+    /// stream can continue to be polled.
+    fn timeout_per_item(self, dur: Duration, handle: &Handle)
+                        -> TimeoutPerItem<Self> {
+        TimeoutPerItem {
+            stream: self,
+            dur: dur,
+            timeout: Some(Timeout::new(dur, handle)),
+        }
+    }
+}
+
+impl<S> StreamTimeoutExt for S
+    where S: Stream,
+          S::Error: From<io::Error>,
+{
+}
+
+/// Future returned by `FutureTimeoutExt::timeout` and
+/// `FutureTimeoutExt::deadline`.
+pub struct Deadline<F> {
+    future: F,
+    timeout: Option<io::Result<Timeout>>,
+}
+
+impl<F> Future for Deadline<F>
+    where F: Future,
+          F::Error: From<io::Error>,
+{
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<F::Item, F::Error> {
+        match self.future.poll() {
+            Ok(Async::NotReady) => {}
+            other => {
+                // Drop the timeout as soon as possible to release its slot on
+                // the event loop.
+                self.timeout = None;
+                return other
+            }
+        }
+
+        let fired = match self.timeout {
+            Some(Ok(ref mut timeout)) => try!(timeout.poll()).is_ready(),
+            Some(Err(_)) => {
+                return Err(self.timeout.take().unwrap().err().unwrap().into())
+            }
+            None => panic!("cannot poll Deadline twice"),
+        };
+        if fired {
+            self.timeout = None;
+            Err(timed_out().into())
+        } else {
+            Ok(Async::NotReady)
+        }
+    }
+}
+
+/// Stream returned by `StreamTimeoutExt::timeout_per_item`.
+pub struct TimeoutPerItem<S> {
+    stream: S,
+    dur: Duration,
+    timeout: Option<io::Result<Timeout>>,
+}
+
+impl<S> Stream for TimeoutPerItem<S>
+    where S: Stream,
+          S::Error: From<io::Error>,
+{
+    type Item = S::Item;
+    type Error = S::Error;
+
+    fn poll(&mut self) -> Poll<Option<S::Item>, S::Error> {
+        let timeout = match self.timeout {
+            Some(Ok(ref mut timeout)) => timeout,
+            Some(Err(_)) => {
+                return Err(self.timeout.take().unwrap().err().unwrap().into())
+            }
+            None => return self.stream.poll(),
+        };
+
+        match self.stream.poll() {
+            Ok(Async::NotReady) => {}
+            Ok(Async::Ready(Some(item))) => {
+                timeout.reset(Instant::now() + self.dur);
+                return Ok(Async::Ready(Some(item)))
+            }
+            Ok(Async::Ready(None)) => {
+                self.timeout = None;
+                return Ok(Async::Ready(None))
+            }
+            Err(e) => {
+                timeout.reset(Instant::now() + self.dur);
+                return Err(e)
+            }
+        }
+
+        if try!(timeout.poll()).is_ready() {
+            timeout.reset(Instant::now() + self.dur);
+            Err(timed_out().into())
+        } else {
+            Ok(Async::NotReady)
+        }
+    }
+}
+
+fn timed_out() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "operation timed out")
+}
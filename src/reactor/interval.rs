@@ -0,0 +1,108 @@
+//! Support for creating futures that represent intervals.
+//!
+//! This module contains the `Interval` type which is a stream that will
+//! resolve at fixed intervals in the future.
+
+use std::io;
+use std::time::{Duration, Instant};
+
+use futures::{Poll, Async};
+use futures::stream::Stream;
+
+use reactor::{Remote, Handle};
+use reactor::timeout_token::TimeoutToken;
+
+/// A stream representing notifications at a fixed interval.
+///
+/// Intervals are created through the `Interval::new` or `Interval::new_at`
+/// methods indicating when a first notification should be triggered and when
+/// it will be repeated.
+///
+/// Ticks are scheduled relative to when the previous tick was *scheduled*
+/// rather than when it was observed, so the interval does not drift over time.
+/// If the stream isn't polled for a while then any missed ticks are yielded
+/// back to back until it has caught up.
+///
+/// Note that intervals are not intended for high resolution timers, but rather
+/// they will likely fire some granularity after the exact instant that they're
+/// otherwise indicated to fire at.
+pub struct Interval {
+    token: TimeoutToken,
+    interval: Duration,
+    handle: Remote,
+}
+
+impl Interval {
+    /// Creates a new interval which will fire every `dur` time.
+    ///
+    /// The first notification is triggered `dur` time into the future. The
+    /// interval reuses a single timeout slot in the event loop for its whole
+    /// lifetime. Like `new_at`, this fails if `dur` is zero.
+    pub fn new(dur: Duration, handle: &Handle) -> io::Result<Interval> {
+        Interval::new_at(Instant::now() + dur, dur, handle)
+    }
+
+    /// Creates a new interval which will fire at `at` and then every `dur`
+    /// time after that.
+    ///
+    /// An error of the kind `InvalidInput` is returned if `dur` is zero, as
+    /// such an interval would fire on every poll and never yield the event
+    /// loop.
+    pub fn new_at(at: Instant, dur: Duration, handle: &Handle)
+                  -> io::Result<Interval> {
+        if dur == Duration::new(0, 0) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "interval period must be nonzero"))
+        }
+        Ok(Interval {
+            token: try!(TimeoutToken::new(at, &handle)),
+            interval: dur,
+            handle: handle.remote().clone(),
+        })
+    }
+
+    /// Returns the period of this interval.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Changes the period of this interval to `dur`.
+    ///
+    /// The new period takes effect immediately: the next tick is rescheduled
+    /// to fire `dur` time from now and subsequent ticks follow every `dur`
+    /// after that.
+    pub fn set_interval(&mut self, dur: Duration) {
+        self.interval = dur;
+        self.reset(Instant::now() + dur);
+    }
+
+    /// Reschedules the next tick of this interval to fire at `at`, after which
+    /// ticks continue to fire at the current period.
+    pub fn reset(&mut self, at: Instant) {
+        self.token.reset_timeout(at, &self.handle);
+    }
+}
+
+impl Stream for Interval {
+    type Item = ();
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<()>, io::Error> {
+        // TODO: is this fast enough?
+        let now = Instant::now();
+        if *self.token.when() <= now {
+            let next = *self.token.when() + self.interval;
+            self.token.reset_timeout(next, &self.handle);
+            Ok(Async::Ready(Some(())))
+        } else {
+            self.token.update_timeout(&self.handle);
+            Ok(Async::NotReady)
+        }
+    }
+}
+
+impl Drop for Interval {
+    fn drop(&mut self) {
+        self.token.cancel_timeout(&self.handle);
+    }
+}
@@ -74,16 +74,16 @@ impl IoToken {
     /// > **Note**: This method should generally not be used directly, but
     /// >           rather the `ReadinessStream` type should be used instead.
     ///
-    /// # Panics
+    /// If the event loop this handle is associated with has gone away then the
+    /// current task will never be notified.
     ///
-    /// This function will panic if the event loop this handle is associated
-    /// with has gone away, or if there is an error communicating with the event
-    /// loop.
+    /// # Panics
     ///
-    /// This function will also panic if there is not a currently running future
+    /// This function will panic if there is not a currently running future
     /// task.
     pub fn schedule_read(&self, handle: &Remote) {
-        handle.send(Message::Schedule(self.token, task::park(), Direction::Read));
+        let msg = Message::Schedule(self.token, task::park(), Direction::Read);
+        drop(handle.send(msg));
     }
 
     /// Schedule the current future task to receive a notification when the
@@ -101,16 +101,16 @@ impl IoToken {
     /// > **Note**: This method should generally not be used directly, but
     /// >           rather the `ReadinessStream` type should be used instead.
     ///
-    /// # Panics
+    /// If the event loop this handle is associated with has gone away then the
+    /// current task will never be notified.
     ///
-    /// This function will panic if the event loop this handle is associated
-    /// with has gone away, or if there is an error communicating with the event
-    /// loop.
+    /// # Panics
     ///
-    /// This function will also panic if there is not a currently running future
+    /// This function will panic if there is not a currently running future
     /// task.
     pub fn schedule_write(&self, handle: &Remote) {
-        handle.send(Message::Schedule(self.token, task::park(), Direction::Write));
+        let msg = Message::Schedule(self.token, task::park(), Direction::Write);
+        drop(handle.send(msg));
     }
 
     /// Unregister all information associated with a token on an event loop,
@@ -130,12 +130,9 @@ impl IoToken {
     /// > **Note**: This method should generally not be used directly, but
     /// >           rather the `ReadinessStream` type should be used instead.
     ///
-    /// # Panics
-    ///
-    /// This function will panic if the event loop this handle is associated
-    /// with has gone away, or if there is an error communicating with the event
-    /// loop.
+    /// If the event loop this handle is associated with has gone away then
+    /// this does nothing.
     pub fn drop_source(&self, handle: &Remote) {
-        handle.send(Message::DropSource(self.token));
+        drop(handle.send(Message::DropSource(self.token)));
     }
 }
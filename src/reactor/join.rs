@@ -0,0 +1,243 @@
+//! Support for waiting on the result of a spawned task.
+//!
+//! This module contains the `JoinHandle` type returned from the `spawn_join`
+//! methods on `Handle` and `Remote`.
+
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use futures::{self, Future, Poll, Async, Complete, Oneshot};
+use futures::task::Unpark;
+
+use reactor::{Core, Inner, Message, Remote};
+
+// Value of `State::token` before the task has been placed in the event loop.
+const NOT_SPAWNED: usize = !0;
+
+/// A future representing the completion of a task spawned onto an event loop.
+///
+/// This is returned from `Handle::spawn_join` and `Remote::spawn_join`, and
+/// resolves to the value the spawned future resolved to. Dropping a
+/// `JoinHandle` does not cancel the task, it will continue to run to
+/// completion in the background. Use `abort` to cancel it instead.
+pub struct JoinHandle<T, E> {
+    rx: Oneshot<Result<T, E>>,
+    state: Arc<State>,
+    remote: Remote,
+}
+
+/// The error returned by a `JoinHandle` if the task didn't complete
+/// successfully.
+#[derive(Debug)]
+pub enum JoinError<E> {
+    /// The task ran to completion and resolved to an error.
+    Failed(E),
+
+    /// The task was cancelled through `JoinHandle::abort` before it completed.
+    Aborted,
+
+    /// The task was dropped before it completed, typically because the event
+    /// loop it was running on was dropped or because it panicked while a
+    /// panic hook was installed with `Core::set_panic_hook`. This is also the
+    /// case if the event loop had reached its limit on the number of tasks,
+    /// see `Builder::max_tasks`.
+    Dropped,
+}
+
+struct State {
+    // Index of the task in `task_dispatch`, or `NOT_SPAWNED`
+    token: AtomicUsize,
+    aborted: AtomicBool,
+    done: AtomicBool,
+}
+
+// The sending half of a `JoinHandle`, which is turned into a `JoinTask` once
+// the future to run is available on the event loop.
+pub struct JoinSender<T, E> {
+    tx: Complete<Result<T, E>>,
+    state: Arc<State>,
+}
+
+// The future actually spawned onto the event loop, which forwards the result
+// of the user's future to the `JoinHandle`.
+struct JoinTask<F: Future> {
+    future: F,
+    tx: Option<Complete<Result<F::Item, F::Error>>>,
+    state: Arc<State>,
+}
+
+pub fn pair<T, E>(remote: &Remote) -> (JoinSender<T, E>, JoinHandle<T, E>) {
+    let (tx, rx) = futures::oneshot();
+    let state = Arc::new(State {
+        token: AtomicUsize::new(NOT_SPAWNED),
+        aborted: AtomicBool::new(false),
+        done: AtomicBool::new(false),
+    });
+    let sender = JoinSender {
+        tx: tx,
+        state: state.clone(),
+    };
+    let handle = JoinHandle {
+        rx: rx,
+        state: state,
+        remote: remote.clone(),
+    };
+    (sender, handle)
+}
+
+impl<T: 'static, E: 'static> JoinSender<T, E> {
+    /// Returns whether the corresponding `JoinHandle` has been aborted.
+    pub fn is_aborted(&self) -> bool {
+        self.state.aborted.load(Ordering::SeqCst)
+    }
+
+    /// Spawns `future` onto the event loop `inner`, sending its result to the
+    /// corresponding `JoinHandle`.
+    ///
+    /// If the handle was already aborted, or the event loop can't take any
+    /// more tasks, then the future is dropped instead.
+    pub fn spawn<F>(self, future: F, inner: &mut Inner)
+        where F: Future<Item=T, Error=E> + 'static,
+    {
+        if self.is_aborted() {
+            return
+        }
+        let state = self.state.clone();
+        let token = inner.spawn(Box::new(JoinTask {
+            future: future,
+            tx: Some(self.tx),
+            state: self.state,
+        }));
+        match token {
+            Ok(token) => state.token.store(token, Ordering::SeqCst),
+            Err(e) => error!("dropping task: {}", e),
+        }
+    }
+}
+
+impl<F: Future> Future for JoinTask<F> {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        // If we were aborted while being polled then `abort` couldn't remove
+        // us from the event loop, so finish now instead. Dropping `tx` tells
+        // the `JoinHandle` we didn't complete.
+        if self.state.aborted.load(Ordering::SeqCst) {
+            return Ok(Async::Ready(()))
+        }
+        let res = match self.future.poll() {
+            Ok(Async::NotReady) => return Ok(Async::NotReady),
+            Ok(Async::Ready(t)) => Ok(t),
+            Err(e) => Err(e),
+        };
+        self.tx.take().unwrap().complete(res);
+        Ok(Async::Ready(()))
+    }
+}
+
+impl<F: Future> Drop for JoinTask<F> {
+    fn drop(&mut self) {
+        // However we're removed from the event loop, our slot may be reused
+        // once we're gone so `abort` must no longer touch it.
+        self.state.done.store(true, Ordering::SeqCst);
+    }
+}
+
+impl<T, E> JoinHandle<T, E> {
+    /// Cancels the task this handle refers to.
+    ///
+    /// The task is removed from its event loop and dropped without being
+    /// polled again, and this handle will then resolve to
+    /// `JoinError::Aborted`. If the task has already completed then this has
+    /// no effect.
+    ///
+    /// Like the `spawn` methods this can be called from any thread, in which
+    /// case the task is removed the next time the event loop turns.
+    pub fn abort(&self) {
+        if self.state.aborted.swap(true, Ordering::SeqCst) {
+            return
+        }
+        let state = self.state.clone();
+        drop(self.remote.send(Message::Run(Box::new(move |lp: &Core| {
+            // If the task hasn't been spawned yet it'll notice the abort
+            // itself, and if it's done its slot may already have been reused.
+            let token = state.token.load(Ordering::SeqCst);
+            if token == NOT_SPAWNED || state.done.load(Ordering::SeqCst) {
+                return
+            }
+            let mut inner = lp.inner.borrow_mut();
+            let polling = match inner.task_dispatch.get(token) {
+                Some(slot) => slot.spawn.is_none(),
+                None => return,
+            };
+            if polling {
+                // We're being called from within the task itself, so it can't
+                // be removed here. Wake it up so it sees it's been aborted.
+                inner.task_dispatch[token].wake.unpark();
+            } else {
+                let task = inner.task_dispatch.remove(token);
+                drop(inner);
+                drop(task);
+            }
+        }))));
+    }
+}
+
+impl<T, E> Future for JoinHandle<T, E> {
+    type Item = T;
+    type Error = JoinError<E>;
+
+    fn poll(&mut self) -> Poll<T, JoinError<E>> {
+        match self.rx.poll() {
+            Ok(Async::Ready(Ok(t))) => Ok(Async::Ready(t)),
+            Ok(Async::Ready(Err(e))) => Err(JoinError::Failed(e)),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Err(_) => {
+                if self.state.aborted.load(Ordering::SeqCst) {
+                    Err(JoinError::Aborted)
+                } else {
+                    Err(JoinError::Dropped)
+                }
+            }
+        }
+    }
+}
+
+impl<T, E> fmt::Debug for JoinHandle<T, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("JoinHandle")
+         .field("aborted", &self.state.aborted.load(Ordering::SeqCst))
+         .field("done", &self.state.done.load(Ordering::SeqCst))
+         .finish()
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for JoinError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            JoinError::Failed(ref e) => write!(f, "task failed: {}", e),
+            JoinError::Aborted => f.write_str("task was aborted"),
+            JoinError::Dropped => f.write_str("task was dropped before completing"),
+        }
+    }
+}
+
+impl<E: Error> Error for JoinError<E> {
+    fn description(&self) -> &str {
+        match *self {
+            JoinError::Failed(ref e) => e.description(),
+            JoinError::Aborted => "task was aborted",
+            JoinError::Dropped => "task was dropped before completing",
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            JoinError::Failed(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
@@ -4,11 +4,15 @@
 //! happening in `tokio-core`. This reactor (or event loop) is used to run
 //! futures, schedule tasks, issue I/O requests, etc.
 
+use std::any::Any;
 use std::cell::RefCell;
+use std::cmp;
+use std::collections::VecDeque;
 use std::io::{self, ErrorKind};
 use std::mem;
+use std::panic::{self, AssertUnwindSafe};
 use std::rc::{Rc, Weak};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
 use std::time::{Instant, Duration};
 
@@ -17,23 +21,42 @@ use futures::task::{self, Unpark, Task, Spawn};
 use mio;
 use slab::Slab;
 
-use heap::{Heap, Slot};
+use wheel::{Wheel, Slot};
 
+mod builder;
 mod channel;
 mod io_token;
+mod ready_queue;
 mod timeout_token;
-use self::channel::{Sender, Receiver, channel};
+use self::channel::{Sender, Receiver};
+use self::ready_queue::{ReadyQueue, TaskWaker};
 
+mod deadline;
+mod interval;
+mod join;
 mod poll_evented;
+mod pool;
+#[cfg(unix)]
+mod raw_fd;
+mod shutdown;
 mod timeout;
+#[cfg(unix)]
+pub mod signal;
+pub use self::builder::Builder;
+pub use self::deadline::{FutureTimeoutExt, StreamTimeoutExt};
+pub use self::deadline::{Deadline, TimeoutPerItem};
+pub use self::interval::Interval;
+pub use self::join::{JoinHandle, JoinError};
 pub use self::poll_evented::PollEvented;
+pub use self::pool::{CorePool, PoolHandle, Balance};
+#[cfg(unix)]
+pub use self::raw_fd::RawFdSource;
+pub use self::shutdown::{ShutdownSignal, ShutdownSummary};
 pub use self::timeout::Timeout;
 
 static NEXT_LOOP_ID: AtomicUsize = ATOMIC_USIZE_INIT;
 scoped_thread_local!(static CURRENT_LOOP: Core);
 
-const SLAB_CAPACITY: usize = 1024 * 64;
-
 /// An event loop.
 ///
 /// The event loop is the main source of blocking in an application which drives
@@ -52,6 +75,33 @@ pub struct Core {
     // it alive.
     _future_registration: mio::Registration,
     future_readiness: Arc<MySetReadiness>,
+
+    // Queue of spawned tasks which have been woken up, along with the
+    // registration used to wake up the poller when a task is woken from
+    // another thread. Tasks taken off the queue are buffered in `ready_tasks`
+    // until they're dispatched.
+    _ready_registration: mio::Registration,
+    ready: Arc<ReadyQueue>,
+    ready_tasks: Vec<usize>,
+
+    // Limits on the amount of work performed in a single turn, along with
+    // the work left over for the next turn when one of them was reached.
+    max_tasks: usize,
+    max_io: usize,
+    max_messages: usize,
+    deferred_io: VecDeque<(usize, mio::Ready)>,
+    messages_pending: bool,
+
+    // If set, panics from spawned tasks are caught and passed to this hook
+    // instead of unwinding out of the event loop.
+    panic_hook: Option<Box<FnMut(usize, Box<Any + Send>)>>,
+
+    // Shared with every `Remote`, along with how long `run_until_shutdown`
+    // waits for tasks once shutdown has been requested.
+    shutdown: Arc<shutdown::State>,
+    shutdown_grace: Duration,
+
+    name: Option<String>,
 }
 
 struct Inner {
@@ -61,6 +111,7 @@ struct Inner {
     // Dispatch slabs for I/O and futures events
     io_dispatch: Slab<ScheduledIo>,
     task_dispatch: Slab<ScheduledTask>,
+    ready: Arc<ReadyQueue>,
 
     // Timer wheel keeping track of all timeouts. The `usize` stored in the
     // timer wheel is an index into the slab below.
@@ -68,8 +119,21 @@ struct Inner {
     // The slab below keeps track of the timeouts themselves as well as the
     // state of the timeout itself. The `TimeoutToken` type is an index into the
     // `timeouts` slab.
-    timer_heap: Heap<(Instant, usize)>,
+    timer_wheel: Wheel<usize>,
     timeouts: Slab<(Option<Slot>, TimeoutState)>,
+
+    // Limits on the size of the slabs above, see `Builder`.
+    max_io_sources: usize,
+    max_tasks: usize,
+    max_timeouts: usize,
+}
+
+/// A summary of the work performed by a single call to `Core::turn`.
+#[derive(Debug, Clone, Copy)]
+pub struct Turn {
+    events: usize,
+    tasks: usize,
+    timeouts: usize,
 }
 
 /// Handle to an event loop, used to construct I/O objects, send messages, and
@@ -81,6 +145,7 @@ struct Inner {
 pub struct Remote {
     id: usize,
     tx: Sender<Message>,
+    shutdown: Arc<shutdown::State>,
 }
 
 /// A non-sendable handle to an event loop, useful for manufacturing instances
@@ -98,9 +163,8 @@ struct ScheduledIo {
 }
 
 struct ScheduledTask {
-    _registration: mio::Registration,
     spawn: Option<Spawn<Box<Future<Item=(), Error=()>>>>,
-    wake: Arc<MySetReadiness>,
+    wake: Arc<TaskWaker>,
 }
 
 enum TimeoutState {
@@ -118,44 +182,40 @@ enum Message {
     DropSource(usize),
     Schedule(usize, Task, Direction),
     UpdateTimeout(usize, Task),
+    ResetTimeout(usize, Instant),
     CancelTimeout(usize),
     Run(Box<FnBox>),
 }
 
 const TOKEN_MESSAGES: mio::Token = mio::Token(0);
 const TOKEN_FUTURE: mio::Token = mio::Token(1);
-const TOKEN_START: usize = 2;
+const TOKEN_READY: mio::Token = mio::Token(2);
+const TOKEN_START: usize = 3;
 
 impl Core {
     /// Creates a new event loop, returning any error that happened during the
     /// creation.
     pub fn new() -> io::Result<Core> {
-        let (tx, rx) = channel();
-        let io = try!(mio::Poll::new());
-        try!(io.register(&rx,
-                         TOKEN_MESSAGES,
-                         mio::Ready::readable(),
-                         mio::PollOpt::edge()));
-        let future_pair = mio::Registration::new(&io,
-                                                 TOKEN_FUTURE,
-                                                 mio::Ready::readable(),
-                                                 mio::PollOpt::level());
-        Ok(Core {
-            events: mio::Events::with_capacity(1024),
-            tx: tx,
-            rx: rx,
-            _future_registration: future_pair.0,
-            future_readiness: Arc::new(MySetReadiness(future_pair.1)),
-
-            inner: Rc::new(RefCell::new(Inner {
-                id: NEXT_LOOP_ID.fetch_add(1, Ordering::Relaxed),
-                io: io,
-                io_dispatch: Slab::with_capacity(SLAB_CAPACITY),
-                task_dispatch: Slab::with_capacity(SLAB_CAPACITY),
-                timeouts: Slab::with_capacity(SLAB_CAPACITY),
-                timer_heap: Heap::new(),
-            })),
-        })
+        Builder::new().build()
+    }
+
+    /// Creates a new event loop whose timers tick at the given resolution.
+    ///
+    /// Timeouts fire on the first tick at or after their deadline, so a coarser
+    /// resolution trades timer precision for fewer wakeups. This is a
+    /// shorthand for `Builder::new().timer_resolution(resolution).build()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `resolution` is zero.
+    pub fn with_timer_resolution(resolution: Duration) -> io::Result<Core> {
+        Builder::new().timer_resolution(resolution).build()
+    }
+
+    /// Returns the name given to this event loop through `Builder::name`, if
+    /// any.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_ref().map(|s| &s[..])
     }
 
     /// Returns a handle to this event loop which cannot be sent across threads
@@ -177,9 +237,85 @@ impl Core {
         Remote {
             id: self.inner.borrow().id,
             tx: self.tx.clone(),
+            shutdown: self.shutdown.clone(),
         }
     }
 
+    /// Enables panic isolation for tasks spawned onto this event loop.
+    ///
+    /// By default a panic in a future spawned with `Handle::spawn` or
+    /// `Remote::spawn` unwinds through the event loop, taking it and every
+    /// other task on it down. Once a hook is installed with this method each
+    /// poll of a spawned task is instead wrapped in `catch_unwind`. If the task
+    /// panics it is removed from the event loop and dropped, and `hook` is
+    /// called with the task's id and the panic payload. The event loop then
+    /// carries on as usual.
+    ///
+    /// The id passed to the hook is the task's index in the event loop, which
+    /// may be reused by later tasks once the task is gone.
+    ///
+    /// Note that the future passed to `run` is not covered by this, panics from
+    /// it always propagate to the caller.
+    pub fn set_panic_hook<F>(&mut self, hook: F)
+        where F: FnMut(usize, Box<Any + Send>) + 'static,
+    {
+        self.panic_hook = Some(Box::new(hook));
+    }
+
+    /// Removes a hook installed with `set_panic_hook`, so panics in spawned
+    /// tasks once again propagate out of the event loop.
+    pub fn take_panic_hook(&mut self) -> Option<Box<FnMut(usize, Box<Any + Send>)>> {
+        self.panic_hook.take()
+    }
+
+    /// Limits the number of spawned tasks polled in a single turn of the event
+    /// loop.
+    ///
+    /// By default every task which is ready gets polled on each turn. With a
+    /// limit in place any tasks beyond it are polled on the following turn
+    /// instead, in the order they were woken up. Note that the event loop will
+    /// not block waiting for events while work is left over.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `max` is zero.
+    pub fn set_max_tasks_per_turn(&mut self, max: usize) {
+        assert!(max > 0, "the task budget must be at least one");
+        self.max_tasks = max;
+    }
+
+    /// Limits the number of I/O events dispatched in a single turn of the
+    /// event loop.
+    ///
+    /// Events beyond the limit are queued up and dispatched at the start of
+    /// the following turn, so a busy socket can't keep the event loop from
+    /// getting to timers and other sockets. By default all events received
+    /// from the poller are dispatched right away.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `max` is zero.
+    pub fn set_max_io_per_turn(&mut self, max: usize) {
+        assert!(max > 0, "the I/O budget must be at least one");
+        self.max_io = max;
+    }
+
+    /// Limits the number of messages from `Remote` handles processed in a
+    /// single turn of the event loop.
+    ///
+    /// Messages are sent to the event loop by `Remote::spawn`, and by I/O
+    /// objects and timeouts used from a task which isn't running on the event
+    /// loop. Messages beyond the limit are left in the queue until the
+    /// following turn. By default the whole queue is processed each turn.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `max` is zero.
+    pub fn set_max_messages_per_turn(&mut self, max: usize) {
+        assert!(max > 0, "the message budget must be at least one");
+        self.max_messages = max;
+    }
+
     /// Runs a future until completion, driving the event loop while we're
     /// otherwise waiting for the future to complete.
     ///
@@ -203,8 +339,28 @@ impl Core {
     /// thread but also to this task, any attempt to poll the future on a
     /// separate thread will result in a panic. That is, calls to
     /// `task::poll_on` must be avoided.
+    ///
+    /// Finally, this method panics if the event loop itself fails, for example
+    /// if polling for events returns an error. Use `try_run` to handle such
+    /// errors instead.
     pub fn run<F>(&mut self, f: F) -> Result<F::Item, F::Error>
         where F: Future,
+    {
+        match self.try_run(f) {
+            Ok(res) => res,
+            Err(e) => panic!("event loop failed: {}", e),
+        }
+    }
+
+    /// Runs a future until completion like `run`, but returns an error if the
+    /// event loop itself fails rather than panicking.
+    ///
+    /// The outer `Result` describes the event loop, and the inner one is the
+    /// result of the future `f`. An error from the event loop is fatal: `f` is
+    /// dropped without completing, and while the `Core` may still be used it's
+    /// usually best to drop it and start over with a new one.
+    pub fn try_run<F>(&mut self, f: F) -> io::Result<Result<F::Item, F::Error>>
+        where F: Future,
     {
         let mut task = task::spawn(f);
         let ready = self.future_readiness.clone();
@@ -214,7 +370,7 @@ impl Core {
         // readiness of the future (as we're about to poll it) and then we check
         // to see if it's done. If it's not then the event loop will turn again.
         let mut res = None;
-        self._run(&mut || {
+        try!(self._run(&mut || {
             assert!(res.is_none());
             match task.poll_future(ready.clone()) {
                 Ok(Async::NotReady) => {}
@@ -222,80 +378,303 @@ impl Core {
                 Err(e) => res = Some(Err(e)),
             }
             res.is_some()
-        });
-        res.expect("run should not return until future is done")
+        }));
+        Ok(res.expect("run should not return until future is done"))
+    }
+
+    /// Performs one iteration of the event loop, blocking on waiting for
+    /// events for at most `max_wait` (forever if `None`).
+    ///
+    /// Each call waits for events to arrive, fires any timeouts which have
+    /// expired, and then dispatches all received events to the tasks and I/O
+    /// objects that are waiting on them.
+    ///
+    /// Unlike `run`, this method will return after exactly one pass over the
+    /// event loop, making it suitable for embedding a `Core` inside another
+    /// event loop or driving it a bounded number of times. The returned `Turn`
+    /// describes the amount of work that was performed.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the event loop fails, see `try_turn` for a
+    /// version which returns the error instead.
+    pub fn turn(&mut self, max_wait: Option<Duration>) -> Turn {
+        match self.try_turn(max_wait) {
+            Ok(turn) => turn,
+            Err(e) => panic!("event loop failed: {}", e),
+        }
+    }
+
+    /// Performs one iteration of the event loop like `turn`, but returns an
+    /// error if the event loop fails rather than panicking.
+    ///
+    /// Errors here are fatal in the same way as those from `try_run`.
+    pub fn try_turn(&mut self, max_wait: Option<Duration>) -> io::Result<Turn> {
+        self.poll(max_wait, &mut || false).map(|(turn, _)| turn)
+    }
+
+    /// Turns the event loop until there's no more work it can do without
+    /// blocking.
+    ///
+    /// This returns once no spawned task has been woken up, no I/O object is
+    /// ready and no timeout has expired, at which point every task is waiting
+    /// on something which hasn't happened yet. Tasks may still be alive when
+    /// this returns, see `run_until_empty` to wait for them to finish.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the event loop fails, like `turn`.
+    pub fn run_until_idle(&mut self) {
+        loop {
+            let turn = self.turn(Some(Duration::new(0, 0)));
+            if turn.events == 0 && turn.tasks == 0 && turn.timeouts == 0 &&
+               !self.has_pending_work() {
+                return
+            }
+        }
+    }
+
+    /// Runs the event loop until all spawned tasks have completed.
+    ///
+    /// This is useful to spawn a number of tasks and then wait for all of them
+    /// to finish, without needing a future to pass to `run`. Tasks spawned
+    /// through a `Remote` before this is called are included. If a task never
+    /// completes then this method never returns.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the event loop fails, like `turn`.
+    pub fn run_until_empty(&mut self) {
+        loop {
+            self.run_until_idle();
+            if self.inner.borrow().task_dispatch.is_empty() {
+                return
+            }
+            self.turn(None);
+        }
+    }
+
+    /// Sets how long `run_until_shutdown` gives tasks to complete once
+    /// shutdown has been requested. Defaults to 30 seconds.
+    pub fn set_shutdown_grace_period(&mut self, grace: Duration) {
+        self.shutdown_grace = grace;
+    }
+
+    /// Runs the event loop until shutdown is requested, and then shuts it down
+    /// gracefully.
+    ///
+    /// This first runs the event loop until `Remote::shutdown` is called,
+    /// returning to the next step straight away if that has already happened.
+    /// At that point every `ShutdownSignal` resolves and listeners stop
+    /// accepting connections, so tasks can finish up what they're doing. The
+    /// event loop keeps running until all tasks have completed, or until the
+    /// grace period set with `set_shutdown_grace_period` runs out, at which
+    /// point the remaining tasks are cancelled by dropping them.
+    ///
+    /// The returned summary says how many tasks had to be cancelled.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the event loop fails, like `run`.
+    pub fn run_until_shutdown(&mut self) -> ShutdownSummary {
+        let _ = self.run(shutdown::signal(&self.shutdown));
+
+        // Pick up anything sent to us before shutdown was requested, such as
+        // tasks spawned through a `Remote`, before deciding if we're done.
+        let deadline = Instant::now() + self.shutdown_grace;
+        self.turn(Some(Duration::new(0, 0)));
+        debug!("shutting down with {} tasks left", self.task_count());
+        loop {
+            if self.task_count() == 0 {
+                break
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                break
+            }
+            self.turn(Some(deadline - now));
+        }
+        shutdown::summary(self.cancel_tasks())
+    }
+
+    // Removes all spawned tasks from the event loop and drops them, returning
+    // how many there were.
+    fn cancel_tasks(&mut self) -> usize {
+        let tasks = {
+            let mut inner = self.inner.borrow_mut();
+            let capacity = inner.task_dispatch.capacity();
+            (0..capacity).filter_map(|i| inner.task_dispatch.remove(i))
+                         .collect::<Vec<_>>()
+        };
+        debug!("cancelling {} tasks", tasks.len());
+        tasks.len()
+    }
+
+    /// Returns the number of tasks currently spawned onto this event loop.
+    pub fn task_count(&self) -> usize {
+        self.inner.borrow().task_dispatch.len()
+    }
+
+    /// Returns the number of I/O objects currently registered with this event
+    /// loop.
+    pub fn io_source_count(&self) -> usize {
+        self.inner.borrow().io_dispatch.len()
+    }
+
+    /// Returns the number of timeouts currently registered with this event
+    /// loop, including ones which have fired but haven't been dropped yet.
+    pub fn timeout_count(&self) -> usize {
+        self.inner.borrow().timeouts.len()
     }
 
-    fn _run(&mut self, done: &mut FnMut() -> bool) {
+    // Returns whether there's work which the next turn will do without
+    // waiting for events.
+    fn has_pending_work(&mut self) -> bool {
+        self.ready.drain_into(&mut self.ready_tasks);
+        !self.ready_tasks.is_empty() ||
+            !self.deferred_io.is_empty() ||
+            self.messages_pending
+    }
+
+    fn _run(&mut self, done: &mut FnMut() -> bool) -> io::Result<()> {
         // Check to see if we're done immediately, if so we shouldn't do any
         // work.
         if CURRENT_LOOP.set(self, || done()) {
-            return
+            return Ok(())
         }
 
-        let mut finished = false;
-        while !finished {
-            let amt;
-            // On Linux, Poll::poll is epoll_wait, which may return EINTR if a
-            // ptracer attaches. This retry loop prevents crashing when
-            // attaching strace, or similar.
-            let start = Instant::now();
-            loop {
-                let inner = self.inner.borrow_mut();
-                let timeout = inner.timer_heap.peek().map(|t| {
-                    if t.0 < start {
-                        Duration::new(0, 0)
-                    } else {
-                        t.0 - start
-                    }
-                });
-                match inner.io.poll(&mut self.events, timeout) {
-                    Ok(a) => {
-                        amt = a;
-                        break;
-                    }
-                    Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
-                    err @ Err(_) => {
-                        err.unwrap();
-                    }
-                }
+        loop {
+            if try!(self.poll(None, done)).1 {
+                return Ok(())
             }
-            debug!("loop poll - {:?}", start.elapsed());
-            debug!("loop time - {:?}", Instant::now());
-
-            // First up, process all timeouts that may have just occurred.
-            let start = Instant::now();
-            self.consume_timeouts(start);
-
-            // Next, process all the events that came in.
-            for i in 0..self.events.len() {
-                let event = self.events.get(i).unwrap();
-                let token = event.token();
-                trace!("event {:?} {:?}", event.kind(), event.token());
-
-                if token == TOKEN_MESSAGES {
-                    CURRENT_LOOP.set(&self, || self.consume_queue());
-                } else if token == TOKEN_FUTURE {
-                    self.future_readiness.0.set_readiness(mio::Ready::none()).unwrap();
-                    if !finished && CURRENT_LOOP.set(self, || done()) {
-                        finished = true;
-                    }
+        }
+    }
+
+    fn poll(&mut self, max_wait: Option<Duration>, done: &mut FnMut() -> bool)
+            -> io::Result<(Turn, bool)> {
+        // Wakeups which failed since the last turn mean we may have missed
+        // work, so report them before doing anything else.
+        if let Some(e) = self.future_readiness.take_error() {
+            return Err(e)
+        }
+        if let Some(e) = self.ready.take_error() {
+            return Err(e)
+        }
+
+        let mut turn = Turn { events: 0, tasks: 0, timeouts: 0 };
+        let amt;
+
+        // If any tasks were woken up since the last turn, or there's work left
+        // over from it, then we shouldn't block waiting for events as there's
+        // already something to do.
+        let max_wait = if self.has_pending_work() {
+            Some(Duration::new(0, 0))
+        } else {
+            max_wait
+        };
+
+        // On Linux, Poll::poll is epoll_wait, which may return EINTR if a
+        // ptracer attaches. This retry loop prevents crashing when
+        // attaching strace, or similar.
+        let start = Instant::now();
+        loop {
+            let inner = self.inner.borrow_mut();
+            let timeout = inner.timer_wheel.next_timeout().map(|t| {
+                if t < start {
+                    Duration::new(0, 0)
                 } else {
-                    self.dispatch(token, event.kind());
+                    t - start
+                }
+            });
+            let timeout = match (timeout, max_wait) {
+                (Some(a), Some(b)) => Some(cmp::min(a, b)),
+                (a, b) => a.or(b),
+            };
+            match inner.io.poll(&mut self.events, timeout) {
+                Ok(a) => {
+                    amt = a;
+                    break;
+                }
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        debug!("loop poll - {:?}", start.elapsed());
+        debug!("loop time - {:?}", Instant::now());
+
+        // First up, process all timeouts that may have just occurred.
+        let start = Instant::now();
+        turn.timeouts = self.consume_timeouts(start);
+
+        // Next, process the events which didn't fit in the last turn's budget,
+        // and then all the events that came in. Events which don't fit in this
+        // turn's budget are deferred until the next.
+        //
+        // Note that a deferred event may refer to an I/O object which has
+        // since been dropped and had its slot reused, in which case the new
+        // object just sees a spurious notification.
+        let mut io_budget = self.max_io;
+        while io_budget > 0 {
+            match self.deferred_io.pop_front() {
+                Some((token, ready)) => self.dispatch_io(token, ready),
+                None => break,
+            }
+            io_budget -= 1;
+        }
+        let mut finished = false;
+        for i in 0..self.events.len() {
+            let event = self.events.get(i).unwrap();
+            let token = event.token();
+            trace!("event {:?} {:?}", event.kind(), event.token());
+
+            if token == TOKEN_MESSAGES {
+                self.messages_pending = true;
+            } else if token == TOKEN_FUTURE {
+                try!(self.future_readiness.reset());
+                if !finished && CURRENT_LOOP.set(self, || done()) {
+                    finished = true;
                 }
+            } else if token == TOKEN_READY {
+                try!(self.ready.clear_notification());
+            } else if io_budget > 0 {
+                self.dispatch_io(usize::from(token) - TOKEN_START, event.kind());
+                io_budget -= 1;
+            } else {
+                self.deferred_io.push_back((usize::from(token) - TOKEN_START,
+                                            event.kind()));
             }
+        }
+        turn.events = amt;
 
-            debug!("loop process - {} events, {:?}", amt, start.elapsed());
+        if self.messages_pending {
+            let max = self.max_messages;
+            self.messages_pending = try!(CURRENT_LOOP.set(&self, || {
+                self.consume_queue(max)
+            }));
         }
+
+        // Finally, run the tasks which have been woken up, either before this
+        // turn or by the events above, up to this turn's budget. Tasks woken up
+        // while these run are left for the next turn.
+        turn.tasks = self.run_tasks();
+
+        debug!("loop process - {} events, {:?}", amt, start.elapsed());
+        Ok((turn, finished))
     }
 
-    fn dispatch(&mut self, token: mio::Token, ready: mio::Ready) {
-        let token = usize::from(token) - TOKEN_START;
-        if token % 2 == 0 {
-            self.dispatch_io(token / 2, ready)
-        } else {
-            self.dispatch_task(token / 2)
+    fn run_tasks(&mut self) -> usize {
+        self.ready.drain_into(&mut self.ready_tasks);
+        let mut tasks = mem::replace(&mut self.ready_tasks, Vec::new());
+        let n = cmp::min(tasks.len(), self.max_tasks);
+        let mut polled = 0;
+        for &index in tasks[..n].iter() {
+            if self.dispatch_task(index) {
+                polled += 1;
+            }
         }
+        tasks.drain(..n);
+        self.ready_tasks = tasks;
+        polled
     }
 
     fn dispatch_io(&mut self, token: usize, ready: mio::Ready) {
@@ -303,7 +682,7 @@ impl Core {
         let mut writer = None;
         let mut inner = self.inner.borrow_mut();
         if let Some(io) = inner.io_dispatch.get_mut(token) {
-            if ready.is_readable() {
+            if is_readable(ready) {
                 reader = io.reader.take();
                 io.readiness.fetch_or(1, Ordering::Relaxed);
             }
@@ -322,19 +701,38 @@ impl Core {
         }
     }
 
-    fn dispatch_task(&mut self, token: usize) {
+    fn dispatch_task(&mut self, token: usize) -> bool {
         let mut inner = self.inner.borrow_mut();
         let (task, wake) = match inner.task_dispatch.get_mut(token) {
             Some(slot) => (slot.spawn.take(), slot.wake.clone()),
-            None => return,
+            None => return false,
         };
-        wake.0.set_readiness(mio::Ready::none()).unwrap();
+        wake.dequeued();
         let mut task = match task {
             Some(task) => task,
-            None => return,
+            None => return false,
         };
         drop(inner);
-        let res = CURRENT_LOOP.set(self, || task.poll_future(wake));
+        let res = if self.panic_hook.is_some() {
+            let res = panic::catch_unwind(AssertUnwindSafe(|| {
+                CURRENT_LOOP.set(self, || task.poll_future(wake))
+            }));
+            match res {
+                Ok(res) => res,
+                Err(payload) => {
+                    debug!("spawned task panicked: {}", token);
+                    let slot = self.inner.borrow_mut().task_dispatch.remove(token);
+                    drop(slot);
+                    drop(task);
+                    if let Some(ref mut hook) = self.panic_hook {
+                        hook(token, payload);
+                    }
+                    return true
+                }
+            }
+        } else {
+            CURRENT_LOOP.set(self, || task.poll_future(wake))
+        };
         inner = self.inner.borrow_mut();
         match res {
             Ok(Async::NotReady) => {
@@ -346,26 +744,28 @@ impl Core {
                 inner.task_dispatch.remove(token).unwrap();
             }
         }
+        true
     }
 
-    fn consume_timeouts(&mut self, now: Instant) {
+    fn consume_timeouts(&mut self, now: Instant) -> usize {
+        let mut fired = 0;
         loop {
             let mut inner = self.inner.borrow_mut();
-            match inner.timer_heap.peek() {
-                Some(head) if head.0 <= now => {}
-                Some(_) => break,
+            let slab_idx = match inner.timer_wheel.poll(now) {
+                Some(idx) => idx,
                 None => break,
             };
-            let (_, slab_idx) = inner.timer_heap.pop().unwrap();
 
             trace!("firing timeout: {}", slab_idx);
             inner.timeouts[slab_idx].0.take().unwrap();
             let handle = inner.timeouts[slab_idx].1.fire();
             drop(inner);
+            fired += 1;
             if let Some(handle) = handle {
                 self.notify_handle(handle);
             }
         }
+        fired
     }
 
     /// Method used to notify a task handle.
@@ -377,12 +777,17 @@ impl Core {
         CURRENT_LOOP.set(&self, || handle.unpark());
     }
 
-    fn consume_queue(&self) {
+    /// Processes up to `max` messages sent to this event loop, returning
+    /// whether any were left in the queue.
+    fn consume_queue(&self, max: usize) -> io::Result<bool> {
         debug!("consuming notification queue");
-        // TODO: can we do better than `.unwrap()` here?
-        while let Some(msg) = self.rx.recv().unwrap() {
-            self.notify(msg);
+        for _ in 0..max {
+            match try!(self.rx.recv()) {
+                Some(msg) => self.notify(msg),
+                None => return Ok(false),
+            }
         }
+        Ok(true)
     }
 
     fn notify(&self, msg: Message) {
@@ -400,6 +805,9 @@ impl Core {
                     self.notify_handle(task);
                 }
             }
+            Message::ResetTimeout(t, at) => {
+                self.inner.borrow_mut().reset_timeout(t, at)
+            }
             Message::CancelTimeout(t) => {
                 self.inner.borrow_mut().cancel_timeout(t)
             }
@@ -417,13 +825,13 @@ impl Inner {
             reader: None,
             writer: None,
         };
-        if self.io_dispatch.vacant_entry().is_none() {
-            let amt = self.io_dispatch.len();
-            self.io_dispatch.reserve_exact(amt);
+        if !reserve(&mut self.io_dispatch, self.max_io_sources) {
+            return Err(io::Error::new(ErrorKind::Other,
+                                      "too many I/O sources on event loop"))
         }
         let entry = self.io_dispatch.vacant_entry().unwrap();
         try!(self.io.register(source,
-                              mio::Token(TOKEN_START + entry.index() * 2),
+                              mio::Token(TOKEN_START + entry.index()),
                               mio::Ready::readable() | mio::Ready::writable(),
                               mio::PollOpt::edge()));
         Ok((sched.readiness.clone(), entry.insert(sched).index()))
@@ -452,12 +860,12 @@ impl Inner {
     }
 
     fn add_timeout(&mut self, at: Instant) -> io::Result<(usize, Instant)> {
-        if self.timeouts.vacant_entry().is_none() {
-            let len = self.timeouts.len();
-            self.timeouts.reserve_exact(len);
+        if !reserve(&mut self.timeouts, self.max_timeouts) {
+            return Err(io::Error::new(ErrorKind::Other,
+                                      "too many timeouts on event loop"))
         }
         let entry = self.timeouts.vacant_entry().unwrap();
-        let slot = self.timer_heap.push((at, entry.index()));
+        let slot = self.timer_wheel.insert(at, entry.index());
         let entry = entry.insert((Some(slot), TimeoutState::NotFired));
         debug!("added a timeout: {}", entry.index());
         Ok((entry.index(), at))
@@ -468,57 +876,89 @@ impl Inner {
         self.timeouts[token].1.block(handle)
     }
 
+    fn reset_timeout(&mut self, token: usize, at: Instant) {
+        debug!("resetting a timeout: {}", token);
+        let pair = &mut self.timeouts[token];
+        if let Some(slot) = pair.0.take() {
+            self.timer_wheel.remove(slot);
+        }
+        pair.0 = Some(self.timer_wheel.insert(at, token));
+        pair.1.reset();
+    }
+
     fn cancel_timeout(&mut self, token: usize) {
         debug!("cancel a timeout: {}", token);
         let pair = self.timeouts.remove(token);
         if let Some((Some(slot), _state)) = pair {
-            self.timer_heap.remove(slot);
+            self.timer_wheel.remove(slot);
         }
     }
 
-    fn spawn(&mut self, future: Box<Future<Item=(), Error=()>>) {
-        if self.task_dispatch.vacant_entry().is_none() {
-            let len = self.task_dispatch.len();
-            self.task_dispatch.reserve_exact(len);
+    // Returns the token of the new task, or an error if the event loop is
+    // already running as many tasks as it's allowed to, in which case the
+    // future is dropped.
+    fn spawn(&mut self, future: Box<Future<Item=(), Error=()>>)
+             -> io::Result<usize> {
+        if !reserve(&mut self.task_dispatch, self.max_tasks) {
+            return Err(io::Error::new(ErrorKind::Other,
+                                      "too many tasks on event loop"))
         }
         let entry = self.task_dispatch.vacant_entry().unwrap();
-        let token = TOKEN_START + 2 * entry.index() + 1;
-        let pair = mio::Registration::new(&self.io,
-                                          mio::Token(token),
-                                          mio::Ready::readable(),
-                                          mio::PollOpt::level());
-        let unpark = Arc::new(MySetReadiness(pair.1));
+        let wake = Arc::new(TaskWaker::new(entry.index(), self.ready.clone()));
         let entry = entry.insert(ScheduledTask {
             spawn: Some(task::spawn(future)),
-            wake: unpark,
-            _registration: pair.0,
+            wake: wake,
         });
         entry.get().wake.clone().unpark();
+        Ok(entry.index())
+    }
+}
+
+/// Makes sure there's room for another entry in `slab`, doubling its capacity
+/// if need be but never growing it past `max` entries. Returns `false` if the
+/// slab is already at its limit.
+fn reserve<T>(slab: &mut Slab<T>, max: usize) -> bool {
+    let len = slab.len();
+    if len >= max {
+        return false
+    }
+    if slab.vacant_entry().is_some() {
+        return true
     }
+    slab.reserve_exact(cmp::min(cmp::max(len, 1), max - len));
+    true
+}
+
+/// Returns whether `ready` should wake up a reader. When the other end of a
+/// pipe or socket goes away the hangup may be reported on its own, and a
+/// reader still needs to be woken up to see the EOF.
+#[cfg(unix)]
+fn is_readable(ready: mio::Ready) -> bool {
+    ready.is_readable() || mio::unix::UnixReady::from(ready).is_hup()
+}
+
+#[cfg(not(unix))]
+fn is_readable(ready: mio::Ready) -> bool {
+    ready.is_readable()
 }
 
 impl Remote {
-    fn send(&self, msg: Message) {
+    /// Sends `msg` to the event loop, running it right away if we're already
+    /// on the event loop.
+    ///
+    /// An error means either that the event loop is gone, in which case `msg`
+    /// has been dropped, or that the event loop couldn't be woken up.
+    fn send(&self, msg: Message) -> io::Result<()> {
         self.with_loop(|lp| {
             match lp {
                 Some(lp) => {
                     // Need to execute all existing requests first, to ensure
                     // that our message is processed "in order"
-                    lp.consume_queue();
+                    try!(lp.consume_queue(::std::usize::MAX));
                     lp.notify(msg);
+                    Ok(())
                 }
-                None => {
-                    match self.tx.send(msg) {
-                        Ok(()) => {}
-
-                        // This should only happen when there was an error
-                        // writing to the pipe to wake up the event loop,
-                        // hopefully that never happens
-                        Err(e) => {
-                            panic!("error sending message to event loop: {}", e)
-                        }
-                    }
-                }
+                None => self.tx.send(msg),
             }
         })
     }
@@ -548,15 +988,76 @@ impl Remote {
     ///
     /// Note that while the closure, `F`, requires the `Send` bound as it might
     /// cross threads, the future `R` does not.
-    pub fn spawn<F, R>(&self, f: F)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the event loop has been dropped, in which case the
+    /// closure is dropped without being run. An error is also returned if the
+    /// event loop couldn't be woken up, although the closure will still run
+    /// the next time the event loop turns.
+    pub fn spawn<F, R>(&self, f: F) -> io::Result<()>
         where F: FnOnce(&Handle) -> R + Send + 'static,
               R: IntoFuture<Item=(), Error=()>,
               R::Future: 'static,
     {
         self.send(Message::Run(Box::new(|lp: &Core| {
             let f = f(&lp.handle());
-            lp.inner.borrow_mut().spawn(Box::new(f.into_future()));
-        })));
+            let res = lp.inner.borrow_mut().spawn(Box::new(f.into_future()));
+            if let Err(e) = res {
+                error!("dropping task: {}", e);
+            }
+        })))
+    }
+
+    /// Spawns a new future into the event loop this handle is associated
+    /// with, returning a handle to its result.
+    ///
+    /// This is like `spawn` except that the future may resolve to any value
+    /// or error, which can then be retrieved through the returned
+    /// `JoinHandle`. The handle can also be used to abort the task.
+    ///
+    /// If the event loop is dropped before the task completes, then the
+    /// handle will resolve to `JoinError::Dropped`.
+    pub fn spawn_join<F, R>(&self, f: F) -> JoinHandle<R::Item, R::Error>
+        where F: FnOnce(&Handle) -> R + Send + 'static,
+              R: IntoFuture,
+              R::Future: 'static,
+              R::Item: Send + 'static,
+              R::Error: Send + 'static,
+    {
+        // Errors are reported through the handle, which resolves to
+        // `JoinError::Dropped` if the closure is dropped without being run.
+        let (tx, handle) = join::pair(self);
+        drop(self.send(Message::Run(Box::new(move |lp: &Core| {
+            if tx.is_aborted() {
+                return
+            }
+            let f = f(&lp.handle());
+            tx.spawn(f.into_future(), &mut lp.inner.borrow_mut());
+        }))));
+        handle
+    }
+
+    /// Requests a graceful shutdown of the event loop.
+    ///
+    /// This resolves every `ShutdownSignal` for the event loop, both existing
+    /// and future ones, and ends the `incoming` streams of listeners on it.
+    /// The event loop itself keeps running, and `Core::run_until_shutdown`
+    /// will then give its remaining tasks a grace period to finish.
+    ///
+    /// Calling this more than once has no further effect.
+    pub fn shutdown(&self) {
+        self.shutdown.request();
+    }
+
+    /// Returns a future which resolves once shutdown of this event loop has
+    /// been requested through `shutdown`.
+    ///
+    /// Tasks which may run for a long time should select on this future and
+    /// finish up when it resolves, so the event loop can shut down without
+    /// having to cancel them. The future never fails.
+    pub fn shutdown_signal(&self) -> ShutdownSignal {
+        shutdown::signal(&self.shutdown)
     }
 }
 
@@ -566,15 +1067,90 @@ impl Handle {
         &self.remote
     }
 
+    // Identifies the event loop this handle refers to, for state which is
+    // shared per event loop elsewhere in the crate.
+    pub(crate) fn id(&self) -> usize {
+        self.remote.id
+    }
+
+    /// Returns a future which resolves once shutdown of this event loop has
+    /// been requested.
+    ///
+    /// See `Remote::shutdown_signal` for more details.
+    pub fn shutdown_signal(&self) -> ShutdownSignal {
+        self.remote.shutdown_signal()
+    }
+
     /// Spawns a new future on the event loop this pin is associated this.
+    ///
+    /// If the event loop is already running as many tasks as it's allowed to
+    /// then the future is dropped and an error is logged. Use `try_spawn` to
+    /// find out whether that happened.
     pub fn spawn<F>(&self, f: F)
         where F: Future<Item=(), Error=()> + 'static,
+    {
+        if let Err(e) = self.try_spawn(f) {
+            error!("dropping task: {}", e);
+        }
+    }
+
+    /// Spawns a new future on the event loop this handle is associated with,
+    /// returning whether it was spawned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the event loop has gone away, or if it's already
+    /// running as many tasks as allowed by `Builder::max_tasks`. In either
+    /// case the future is dropped without being run.
+    pub fn try_spawn<F>(&self, f: F) -> io::Result<()>
+        where F: Future<Item=(), Error=()> + 'static,
     {
         let inner = match self.inner.upgrade() {
             Some(inner) => inner,
-            None => return,
+            None => {
+                return Err(io::Error::new(ErrorKind::Other, "event loop gone"))
+            }
         };
-        inner.borrow_mut().spawn(Box::new(f));
+        let res = inner.borrow_mut().spawn(Box::new(f));
+        res.map(|_| ())
+    }
+
+    /// Spawns a new future on the event loop this handle is associated with,
+    /// returning a handle to its result.
+    ///
+    /// See `Remote::spawn_join` for more details. If the event loop has
+    /// already gone away then the returned handle resolves immediately to
+    /// `JoinError::Dropped`.
+    pub fn spawn_join<F>(&self, f: F) -> JoinHandle<F::Item, F::Error>
+        where F: Future + 'static,
+    {
+        let (tx, handle) = join::pair(&self.remote);
+        if let Some(inner) = self.inner.upgrade() {
+            tx.spawn(f, &mut inner.borrow_mut());
+        }
+        handle
+    }
+}
+
+impl Turn {
+    /// Returns the number of events received from the underlying poller
+    /// during this turn.
+    ///
+    /// This includes the event loop's own internal wakeups, such as those for
+    /// messages sent through a `Remote` or tasks woken from another thread,
+    /// not just readiness events for I/O objects.
+    pub fn events(&self) -> usize {
+        self.events
+    }
+
+    /// Returns the number of spawned tasks that were polled during this turn.
+    pub fn tasks(&self) -> usize {
+        self.tasks
+    }
+
+    /// Returns the number of timeouts that fired during this turn.
+    pub fn timeouts(&self) -> usize {
+        self.timeouts
     }
 }
 
@@ -588,6 +1164,12 @@ impl TimeoutState {
         None
     }
 
+    fn reset(&mut self) {
+        if let TimeoutState::Fired = *self {
+            *self = TimeoutState::NotFired;
+        }
+    }
+
     fn fire(&mut self) -> Option<Task> {
         match mem::replace(self, TimeoutState::Fired) {
             TimeoutState::NotFired => None,
@@ -597,12 +1179,39 @@ impl TimeoutState {
     }
 }
 
-struct MySetReadiness(mio::SetReadiness);
+struct MySetReadiness {
+    readiness: mio::SetReadiness,
+
+    // The first error encountered while unparking, which can't be returned
+    // from `unpark` so is instead picked up by the event loop.
+    error: Mutex<Option<io::Error>>,
+}
+
+impl MySetReadiness {
+    fn new(readiness: mio::SetReadiness) -> MySetReadiness {
+        MySetReadiness {
+            readiness: readiness,
+            error: Mutex::new(None),
+        }
+    }
+
+    fn reset(&self) -> io::Result<()> {
+        self.readiness.set_readiness(mio::Ready::none())
+    }
+
+    fn take_error(&self) -> Option<io::Error> {
+        self.error.lock().unwrap().take()
+    }
+}
 
 impl Unpark for MySetReadiness {
     fn unpark(&self) {
-        self.0.set_readiness(mio::Ready::readable())
-              .expect("failed to set readiness");
+        if let Err(e) = self.readiness.set_readiness(mio::Ready::readable()) {
+            let mut error = self.error.lock().unwrap();
+            if error.is_none() {
+                *error = Some(e);
+            }
+        }
     }
 }
 
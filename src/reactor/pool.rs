@@ -0,0 +1,354 @@
+//! A pool of event loops, each running on its own thread.
+
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use futures::{Future, IntoFuture, Poll};
+
+use reactor::{Builder, Handle, Remote, ShutdownSummary};
+use reactor::shutdown;
+
+/// A pool of event loops, each running on a dedicated thread.
+///
+/// A single `Core` only ever uses one thread. A `CorePool` starts a number of
+/// threads each running their own `Core`, and hands out a `PoolHandle` which
+/// spreads spawned tasks across them. Tasks are still pinned to the event loop
+/// they were spawned onto for their whole lifetime.
+///
+/// The event loops run until shutdown is requested through `shutdown` or
+/// `PoolHandle::shutdown`, at which point they shut down gracefully as
+/// described in `Core::run_until_shutdown`. Dropping a `CorePool` also
+/// requests shutdown, but doesn't wait for the threads to exit.
+///
+/// # Examples
+///
+/// ```
+/// extern crate futures;
+/// extern crate tokio_core;
+///
+/// use futures::Future;
+/// use tokio_core::reactor::CorePool;
+///
+/// fn main() {
+///     let pool = CorePool::new(2).unwrap();
+///     let (tx, rx) = futures::oneshot();
+///     pool.handle().spawn(|_| {
+///         tx.complete(1);
+///         Ok(())
+///     }).unwrap();
+///     assert_eq!(rx.wait().unwrap(), 1);
+///
+///     let summaries = pool.shutdown().unwrap();
+///     assert_eq!(summaries.len(), 2);
+/// }
+/// ```
+pub struct CorePool {
+    handle: PoolHandle,
+    threads: Vec<thread::JoinHandle<ShutdownSummary>>,
+}
+
+/// A cloneable, sendable handle used to spawn tasks onto a `CorePool`.
+#[derive(Clone)]
+pub struct PoolHandle {
+    inner: Arc<Inner>,
+}
+
+/// The strategy a `CorePool` uses to pick an event loop for each task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Balance {
+    /// Each task goes to the next event loop in turn.
+    RoundRobin,
+
+    /// Each task goes to the event loop with the fewest live tasks spawned
+    /// through the pool, as reported by `PoolHandle::pool_task_count`.
+    ///
+    /// Only tasks spawned with `PoolHandle::spawn` or `spawn_on` are counted.
+    /// Tasks which those spawn in turn through the event loop's own `Handle`
+    /// or `Remote` aren't, so this balances the pool's own work rather than
+    /// the total number of tasks on each event loop.
+    LeastLoaded,
+}
+
+struct Inner {
+    remotes: Vec<Remote>,
+
+    // Number of tasks spawned through the pool which are still alive on each
+    // event loop.
+    pool_tasks: Vec<Arc<AtomicUsize>>,
+    next: AtomicUsize,
+    balance: Balance,
+}
+
+// Spawned along with each task and dropped with it, keeping
+// `Inner::pool_tasks` up to date.
+struct CountGuard(Arc<AtomicUsize>);
+
+struct Counted<F> {
+    future: F,
+    _guard: CountGuard,
+}
+
+impl CorePool {
+    /// Starts a pool of `size` event loops, spreading tasks across them
+    /// round-robin.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `size` is zero.
+    pub fn new(size: usize) -> io::Result<CorePool> {
+        CorePool::with_config(size, Balance::RoundRobin, |_, _| {})
+    }
+
+    /// Starts a pool of `size` event loops using the `balance` strategy.
+    ///
+    /// Each event loop is created from a `Builder` on its own thread, which
+    /// `config` is called with along with the index of the event loop so it
+    /// can be customized. By default the event loops, and their threads, are
+    /// named `core-pool-N`.
+    ///
+    /// If any of the event loops fails to start then the ones which did start
+    /// are shut down, and the error is returned.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `size` is zero.
+    pub fn with_config<F>(size: usize, balance: Balance, config: F)
+                          -> io::Result<CorePool>
+        where F: Fn(usize, &mut Builder) + Send + Sync + 'static,
+    {
+        assert!(size > 0, "a pool needs at least one event loop");
+        let config = Arc::new(config);
+        let mut threads = Vec::with_capacity(size);
+        let mut remotes = Vec::with_capacity(size);
+        let mut error = None;
+        for i in 0..size {
+            let (tx, rx) = mpsc::channel();
+            let config = config.clone();
+            let name = format!("core-pool-{}", i);
+            let builder = thread::Builder::new().name(name.clone());
+            let thread = builder.spawn(move || {
+                let mut builder = Builder::new();
+                builder.name(name);
+                config(i, &mut builder);
+                let mut core = match builder.build() {
+                    Ok(core) => core,
+                    Err(e) => {
+                        drop(tx.send(Err(e)));
+                        return shutdown::summary(0)
+                    }
+                };
+                drop(tx.send(Ok(core.remote())));
+                core.run_until_shutdown()
+            });
+            match thread {
+                Ok(thread) => threads.push(thread),
+                Err(e) => {
+                    error = Some(e);
+                    break
+                }
+            }
+            match rx.recv() {
+                Ok(Ok(remote)) => remotes.push(remote),
+                Ok(Err(e)) => {
+                    error = Some(e);
+                    break
+                }
+                Err(_) => {
+                    error = Some(io::Error::new(io::ErrorKind::Other,
+                                                "event loop thread panicked"));
+                    break
+                }
+            }
+        }
+
+        let pool = CorePool {
+            handle: PoolHandle {
+                inner: Arc::new(Inner {
+                    pool_tasks: remotes.iter()
+                                       .map(|_| Arc::new(AtomicUsize::new(0)))
+                                       .collect(),
+                    remotes: remotes,
+                    next: AtomicUsize::new(0),
+                    balance: balance,
+                }),
+            },
+            threads: threads,
+        };
+        match error {
+            Some(e) => {
+                drop(pool.shutdown());
+                Err(e)
+            }
+            None => Ok(pool),
+        }
+    }
+
+    /// Returns a handle which can be used to spawn tasks onto this pool.
+    pub fn handle(&self) -> PoolHandle {
+        self.handle.clone()
+    }
+
+    /// Requests shutdown of all event loops in this pool and waits for their
+    /// threads to exit, returning a summary of each event loop's shutdown.
+    ///
+    /// An error is returned if any of the threads panicked.
+    pub fn shutdown(mut self) -> thread::Result<Vec<ShutdownSummary>> {
+        self.handle.shutdown();
+        self.join_threads()
+    }
+
+    /// Waits for the threads of this pool to exit, which happens once
+    /// shutdown has been requested through a `PoolHandle`.
+    ///
+    /// An error is returned if any of the threads panicked.
+    pub fn join(mut self) -> thread::Result<Vec<ShutdownSummary>> {
+        self.join_threads()
+    }
+
+    fn join_threads(&mut self) -> thread::Result<Vec<ShutdownSummary>> {
+        let mut summaries = Vec::with_capacity(self.threads.len());
+        let mut res = Ok(());
+        for thread in self.threads.drain(..) {
+            match thread.join() {
+                Ok(summary) => summaries.push(summary),
+                Err(e) => res = Err(e),
+            }
+        }
+        res.map(|()| summaries)
+    }
+}
+
+impl Drop for CorePool {
+    fn drop(&mut self) {
+        if !self.threads.is_empty() {
+            self.handle.shutdown();
+        }
+    }
+}
+
+impl fmt::Debug for CorePool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CorePool")
+         .field("size", &self.handle.len())
+         .field("balance", &self.handle.inner.balance)
+         .finish()
+    }
+}
+
+impl PoolHandle {
+    /// Returns the number of event loops in the pool.
+    pub fn len(&self) -> usize {
+        self.inner.remotes.len()
+    }
+
+    /// Returns the remote handle of the `index`th event loop in the pool.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `index` is out of bounds.
+    pub fn remote(&self, index: usize) -> &Remote {
+        &self.inner.remotes[index]
+    }
+
+    /// Returns the number of tasks spawned through the pool which are still
+    /// alive on the `index`th event loop.
+    ///
+    /// This is what `Balance::LeastLoaded` goes by. Tasks spawned onto the
+    /// event loop in any other way aren't included, see `Core::task_count`
+    /// for the total.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `index` is out of bounds.
+    pub fn pool_task_count(&self, index: usize) -> usize {
+        self.inner.pool_tasks[index].load(Ordering::SeqCst)
+    }
+
+    /// Spawns a new future onto one of the event loops in the pool, picked
+    /// according to the pool's `Balance` strategy.
+    ///
+    /// This works just like `Remote::spawn`, and returns an error in the same
+    /// cases.
+    pub fn spawn<F, R>(&self, f: F) -> io::Result<()>
+        where F: FnOnce(&Handle) -> R + Send + 'static,
+              R: IntoFuture<Item=(), Error=()>,
+              R::Future: 'static,
+    {
+        let index = self.pick();
+        self.spawn_on(index, f)
+    }
+
+    /// Spawns a new future onto the `index`th event loop in the pool.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `index` is out of bounds.
+    pub fn spawn_on<F, R>(&self, index: usize, f: F) -> io::Result<()>
+        where F: FnOnce(&Handle) -> R + Send + 'static,
+              R: IntoFuture<Item=(), Error=()>,
+              R::Future: 'static,
+    {
+        let count = self.inner.pool_tasks[index].clone();
+        count.fetch_add(1, Ordering::SeqCst);
+        let guard = CountGuard(count);
+        self.inner.remotes[index].spawn(move |handle| {
+            Counted {
+                future: f(handle).into_future(),
+                _guard: guard,
+            }
+        })
+    }
+
+    /// Requests shutdown of all event loops in the pool.
+    ///
+    /// See `Remote::shutdown` for more details.
+    pub fn shutdown(&self) {
+        for remote in self.inner.remotes.iter() {
+            remote.shutdown();
+        }
+    }
+
+    fn pick(&self) -> usize {
+        let len = self.len();
+        let start = self.inner.next.fetch_add(1, Ordering::Relaxed) % len;
+        match self.inner.balance {
+            Balance::RoundRobin => start,
+
+            // Start scanning at a different event loop each time so ties are
+            // broken evenly.
+            Balance::LeastLoaded => {
+                (0..len).map(|i| (start + i) % len)
+                        .min_by_key(|&i| self.pool_task_count(i))
+                        .unwrap()
+            }
+        }
+    }
+}
+
+impl fmt::Debug for PoolHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PoolHandle")
+         .field("size", &self.len())
+         .field("balance", &self.inner.balance)
+         .finish()
+    }
+}
+
+impl Drop for CountGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl<F: Future> Future for Counted<F> {
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<F::Item, F::Error> {
+        self.future.poll()
+    }
+}
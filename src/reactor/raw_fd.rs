@@ -0,0 +1,165 @@
+//! Registration of arbitrary file descriptors with an event loop.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::os::unix::prelude::*;
+
+use libc;
+use mio;
+use mio::unix::EventedFd;
+
+use reactor::{Handle, PollEvented};
+
+/// A raw file descriptor which can be registered with an event loop.
+///
+/// This is a thin wrapper which implements `mio::Evented` for any file
+/// descriptor, such as an eventfd, timerfd, inotify instance, tun device or a
+/// pipe created by another library. Wrapped in a `PollEvented` it can then be
+/// used as an `Io` object, with reads and writes going directly through
+/// `libc::read` and `libc::write`.
+///
+/// The descriptor must already be in nonblocking mode, otherwise reads and
+/// writes will block the event loop.
+///
+/// A `RawFdSource` created with `new` merely borrows the descriptor and will
+/// not close it, whereas one created with `FromRawFd::from_raw_fd` owns the
+/// descriptor and closes it when dropped.
+pub struct RawFdSource {
+    fd: RawFd,
+    owned: bool,
+}
+
+impl RawFdSource {
+    /// Creates a new source for `fd` which does not take ownership of it.
+    ///
+    /// The caller is responsible for keeping the descriptor open for as long
+    /// as this source (or the `PollEvented` wrapping it) is alive, and for
+    /// closing it afterwards. Note that the descriptor remains in the event
+    /// loop's poll set until it is closed, so it can't be registered again in
+    /// the meantime.
+    pub fn new(fd: RawFd) -> RawFdSource {
+        RawFdSource { fd: fd, owned: false }
+    }
+
+    /// Returns whether this source will close its descriptor when dropped.
+    pub fn is_owned(&self) -> bool {
+        self.owned
+    }
+}
+
+impl PollEvented<RawFdSource> {
+    /// Registers the nonblocking descriptor `fd` with the event loop that
+    /// `handle` refers to, without taking ownership of it.
+    ///
+    /// This is a shorthand for `PollEvented::new(RawFdSource::new(fd), handle)`.
+    pub fn from_raw_fd(fd: RawFd, handle: &Handle)
+                       -> io::Result<PollEvented<RawFdSource>> {
+        PollEvented::new(RawFdSource::new(fd), handle)
+    }
+}
+
+impl AsRawFd for RawFdSource {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl FromRawFd for RawFdSource {
+    unsafe fn from_raw_fd(fd: RawFd) -> RawFdSource {
+        RawFdSource { fd: fd, owned: true }
+    }
+}
+
+impl IntoRawFd for RawFdSource {
+    fn into_raw_fd(mut self) -> RawFd {
+        self.owned = false;
+        self.fd
+    }
+}
+
+impl Read for RawFdSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&*self).read(buf)
+    }
+}
+
+impl Write for RawFdSource {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> Read for &'a RawFdSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = unsafe {
+            libc::read(self.fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len())
+        };
+        if n < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(n as usize)
+        }
+    }
+}
+
+impl<'a> Write for &'a RawFdSource {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = unsafe {
+            libc::write(self.fd, buf.as_ptr() as *const libc::c_void, buf.len())
+        };
+        if n < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(n as usize)
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl mio::Evented for RawFdSource {
+    fn register(&self,
+                poll: &mio::Poll,
+                token: mio::Token,
+                interest: mio::Ready,
+                opts: mio::PollOpt) -> io::Result<()> {
+        EventedFd(&self.fd).register(poll, token, interest, opts)
+    }
+
+    fn reregister(&self,
+                  poll: &mio::Poll,
+                  token: mio::Token,
+                  interest: mio::Ready,
+                  opts: mio::PollOpt) -> io::Result<()> {
+        EventedFd(&self.fd).reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &mio::Poll) -> io::Result<()> {
+        EventedFd(&self.fd).deregister(poll)
+    }
+}
+
+impl fmt::Debug for RawFdSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RawFdSource")
+         .field("fd", &self.fd)
+         .field("owned", &self.owned)
+         .finish()
+    }
+}
+
+impl Drop for RawFdSource {
+    fn drop(&mut self) {
+        if self.owned {
+            unsafe {
+                libc::close(self.fd);
+            }
+        }
+    }
+}
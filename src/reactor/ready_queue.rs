@@ -0,0 +1,130 @@
+//! The queue of spawned tasks which are ready to be polled.
+//!
+//! Each task spawned onto an event loop gets a `TaskWaker` as its unpark
+//! handle. Waking a task pushes its index onto the event loop's `ReadyQueue`,
+//! which is drained once per turn of the event loop. Wakeups which happen on
+//! the event loop itself (the common case, for example an I/O object becoming
+//! ready) never touch the underlying poller. Only wakeups from other threads
+//! need to interrupt the poller, and those are coalesced so a burst of them
+//! only results in one notification.
+
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use futures::task::Unpark;
+use mio;
+
+use mpsc_queue::{Queue, PopResult};
+use reactor::CURRENT_LOOP;
+
+pub struct ReadyQueue {
+    queue: Queue<usize>,
+    readiness: mio::SetReadiness,
+
+    // Whether the poller has been notified of new tasks in `queue` but the
+    // event loop hasn't yet seen that notification.
+    notified: AtomicBool,
+
+    // The first error encountered while notifying the poller, which can't be
+    // returned from `unpark` so is instead picked up by the event loop.
+    error: Mutex<Option<io::Error>>,
+}
+
+pub struct TaskWaker {
+    index: usize,
+    queued: AtomicBool,
+    ready: Arc<ReadyQueue>,
+}
+
+impl ReadyQueue {
+    pub fn new(readiness: mio::SetReadiness) -> ReadyQueue {
+        ReadyQueue {
+            queue: Queue::new(),
+            readiness: readiness,
+            notified: AtomicBool::new(false),
+            error: Mutex::new(None),
+        }
+    }
+
+    /// Acknowledges a notification from the poller, after which other threads
+    /// pushing onto the queue will notify it again.
+    ///
+    /// This must be called before the queue is drained.
+    pub fn clear_notification(&self) -> io::Result<()> {
+        // Reset readiness first, so a thread which notifies us after we've
+        // reset `notified` below sets it again. Swapping rather than storing
+        // also synchronizes with any push made before that thread notified us,
+        // so the drain afterwards will see it.
+        try!(self.readiness.set_readiness(mio::Ready::none()));
+        self.notified.swap(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Returns the first error encountered while notifying the poller of a
+    /// task being woken up, if any.
+    pub fn take_error(&self) -> Option<io::Error> {
+        self.error.lock().unwrap().take()
+    }
+
+    /// Moves the indices of all tasks which are ready into `dst`.
+    ///
+    /// This may only be called from the event loop which owns this queue.
+    pub fn drain_into(&self, dst: &mut Vec<usize>) {
+        loop {
+            // Only the event loop pops from the queue, and it's not `Send`.
+            match unsafe { self.queue.pop() } {
+                PopResult::Data(index) => dst.push(index),
+
+                // An inconsistent queue means another thread is in the middle
+                // of pushing, and it'll notify the poller once it's done.
+                PopResult::Empty |
+                PopResult::Inconsistent => break,
+            }
+        }
+    }
+
+    fn push(ready: &Arc<ReadyQueue>, index: usize) {
+        ready.queue.push(index);
+
+        // If we're running on the event loop which owns this queue then it'll
+        // drain the queue before it next blocks, so there's no need to wake it.
+        let on_loop = CURRENT_LOOP.is_set() &&
+                      CURRENT_LOOP.with(|lp| &*lp.ready as *const _ == &**ready as *const _);
+        if on_loop {
+            return
+        }
+        if !ready.notified.swap(true, Ordering::SeqCst) {
+            if let Err(e) = ready.readiness.set_readiness(mio::Ready::readable()) {
+                let mut error = ready.error.lock().unwrap();
+                if error.is_none() {
+                    *error = Some(e);
+                }
+            }
+        }
+    }
+}
+
+impl TaskWaker {
+    pub fn new(index: usize, ready: Arc<ReadyQueue>) -> TaskWaker {
+        TaskWaker {
+            index: index,
+            queued: AtomicBool::new(false),
+            ready: ready,
+        }
+    }
+
+    /// Called when this task is about to be polled, after which it can be
+    /// pushed onto the ready queue again.
+    pub fn dequeued(&self) {
+        self.queued.store(false, Ordering::SeqCst);
+    }
+}
+
+impl Unpark for TaskWaker {
+    fn unpark(&self) {
+        if !self.queued.swap(true, Ordering::SeqCst) {
+            ReadyQueue::push(&self.ready, self.index);
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! Support for shutting down an event loop gracefully.
+//!
+//! Shutdown is requested through `Remote::shutdown`, after which every
+//! `ShutdownSignal` resolves so tasks can wind down, and
+//! `Core::run_until_shutdown` gives the tasks left on the event loop a grace
+//! period to finish before cancelling them.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use futures::{Future, Poll, Async};
+use futures::task::{self, Task};
+
+/// A future which resolves once shutdown of an event loop has been requested.
+///
+/// This is created by `Handle::shutdown_signal` or `Remote::shutdown_signal`,
+/// and resolves after a call to `Remote::shutdown` for the same event loop.
+/// Long-running tasks, such as one serving a connection, can select on it to
+/// find out when they should finish what they're doing and exit.
+pub struct ShutdownSignal {
+    id: usize,
+    state: Arc<State>,
+}
+
+/// A summary of a graceful shutdown, returned from
+/// `Core::run_until_shutdown`.
+#[derive(Debug, Clone, Copy)]
+pub struct ShutdownSummary {
+    cancelled: usize,
+}
+
+pub struct State {
+    requested: AtomicBool,
+    next_id: AtomicUsize,
+
+    // Tasks waiting on a `ShutdownSignal`, keyed by the signal's id.
+    waiters: Mutex<HashMap<usize, Task>>,
+}
+
+impl State {
+    pub fn new() -> State {
+        State {
+            requested: AtomicBool::new(false),
+            next_id: AtomicUsize::new(0),
+            waiters: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+
+    /// Requests shutdown, waking up everything waiting on a signal.
+    pub fn request(&self) {
+        if self.requested.swap(true, Ordering::SeqCst) {
+            return
+        }
+        debug!("shutdown requested");
+        let waiters = mem::replace(&mut *self.waiters.lock().unwrap(),
+                                   HashMap::new());
+        for (_, task) in waiters {
+            task.unpark();
+        }
+    }
+}
+
+pub fn signal(state: &Arc<State>) -> ShutdownSignal {
+    ShutdownSignal {
+        id: state.next_id.fetch_add(1, Ordering::Relaxed),
+        state: state.clone(),
+    }
+}
+
+impl ShutdownSignal {
+    /// Returns whether shutdown has been requested, without registering
+    /// interest in it.
+    pub fn is_requested(&self) -> bool {
+        self.state.is_requested()
+    }
+}
+
+impl Future for ShutdownSignal {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        if self.state.is_requested() {
+            return Ok(Async::Ready(()))
+        }
+        self.state.waiters.lock().unwrap().insert(self.id, task::park());
+
+        // Check again now that we're registered, in case shutdown was
+        // requested in the meantime and we missed being woken up.
+        if self.state.is_requested() {
+            self.state.waiters.lock().unwrap().remove(&self.id);
+            Ok(Async::Ready(()))
+        } else {
+            Ok(Async::NotReady)
+        }
+    }
+}
+
+impl Drop for ShutdownSignal {
+    fn drop(&mut self) {
+        self.state.waiters.lock().unwrap().remove(&self.id);
+    }
+}
+
+impl fmt::Debug for ShutdownSignal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ShutdownSignal")
+         .field("requested", &self.is_requested())
+         .finish()
+    }
+}
+
+pub fn summary(cancelled: usize) -> ShutdownSummary {
+    ShutdownSummary { cancelled: cancelled }
+}
+
+impl ShutdownSummary {
+    /// Returns the number of tasks which were still running at the end of the
+    /// grace period, and so were cancelled.
+    pub fn cancelled(&self) -> usize {
+        self.cancelled
+    }
+
+    /// Returns whether all tasks completed within the grace period.
+    pub fn is_graceful(&self) -> bool {
+        self.cancelled == 0
+    }
+}
@@ -0,0 +1,354 @@
+//! Unix signal handling for an event loop.
+//!
+//! This module contains the `Signal` type which is a stream of notifications
+//! that a particular Unix signal has been delivered to this process.
+//!
+//! Signal handlers are installed process-wide the first time a `Signal` is
+//! created for a particular signal number. The handler records the delivery and
+//! writes a byte to a global self-pipe, and every `Signal` stream (on any event
+//! loop) watches its own duplicate of the reading end of that pipe. This means
+//! that any number of listeners across any number of `Core`s can observe the
+//! same signal. Because an event loop may miss its wakeup if another one
+//! drains the pipe first, whichever stream reads bytes from the pipe also
+//! notifies all other tasks waiting on the signals it read.
+//!
+//! Note that signals are coalesced: if a signal is delivered several times
+//! before a `Signal` stream is polled then the stream will only yield one
+//! notification.
+
+use std::cell::UnsafeCell;
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::mem;
+use std::os::unix::prelude::*;
+use std::sync::{Mutex, Once, ONCE_INIT};
+use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+
+use futures::{Poll, Async};
+use futures::stream::Stream;
+use futures::task::{self, Task};
+use libc;
+use mio::unix::EventedFd;
+
+use reactor::{Core, Handle, Message, PollEvented, RawFdSource};
+
+pub use libc::{SIGALRM, SIGCHLD, SIGHUP, SIGINT, SIGPIPE, SIGQUIT, SIGTERM};
+pub use libc::{SIGUSR1, SIGUSR2, SIGWINCH};
+
+// Signals which can't be caught or which can't be handled in a meaningful way
+// by writing to a pipe and returning.
+const FORBIDDEN: &'static [i32] = &[
+    libc::SIGILL,
+    libc::SIGFPE,
+    libc::SIGKILL,
+    libc::SIGSEGV,
+    libc::SIGSTOP,
+];
+
+// Large enough for the real-time signals on Linux
+const MAX_SIGNUM: usize = 65;
+
+static NEXT_SIGNAL_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+static INIT: Once = ONCE_INIT;
+static mut GLOBALS: *const Globals = 0 as *const Globals;
+
+struct Globals {
+    sender: RawFd,
+    receiver: RawFd,
+    signals: Vec<SignalInfo>,
+}
+
+struct SignalInfo {
+    // Incremented by the signal handler each time the signal is delivered
+    generation: AtomicUsize,
+    init: Once,
+    init_err: UnsafeCell<Option<i32>>,
+    prev: UnsafeCell<libc::sigaction>,
+    // Tasks blocked on a `Signal` stream for this signal, keyed by stream
+    waiters: Mutex<HashMap<usize, Task>>,
+}
+
+// The `UnsafeCell` fields are only written once, guarded by `init`, before the
+// signal handler which reads them is installed.
+unsafe impl Sync for Globals {}
+
+/// A stream of notifications that a particular Unix signal was delivered.
+///
+/// Created by `Signal::new`, each item yielded by this stream is the signal
+/// number that it was created for. Only deliveries after the stream was
+/// created are reported, and multiple deliveries between polls are coalesced
+/// into one notification.
+pub struct Signal {
+    id: usize,
+    signum: i32,
+    generation: usize,
+    io: PollEvented<RawFdSource>,
+    fd: Option<Fd>,
+}
+
+// Our duplicate of the reading end of the self-pipe. It's registered with
+// the event loop through a borrowing `RawFdSource` while `Fd` owns it.
+struct Fd(RawFd);
+
+impl Signal {
+    /// Creates a new stream which will receive notifications when the current
+    /// process receives the signal `signum`.
+    ///
+    /// The first time this is called for a particular signal a process-wide
+    /// signal handler is installed. Any handler which was previously installed
+    /// for that signal will continue to be invoked after ours.
+    ///
+    /// An error is returned if `signum` is not a valid signal or is one which
+    /// cannot be handled, such as `SIGKILL` or `SIGSEGV`.
+    pub fn new(signum: i32, handle: &Handle) -> io::Result<Signal> {
+        if signum <= 0 || signum as usize >= MAX_SIGNUM ||
+           FORBIDDEN.contains(&signum) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "invalid or unsupported signal"))
+        }
+        let globals = try!(globals());
+        try!(install(globals, signum));
+
+        let fd = unsafe {
+            libc::fcntl(globals.receiver, libc::F_DUPFD_CLOEXEC, 0)
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        let fd = Fd(fd);
+        let info = &globals.signals[signum as usize];
+        Ok(Signal {
+            id: NEXT_SIGNAL_ID.fetch_add(1, Ordering::Relaxed),
+            signum: signum,
+            generation: info.generation.load(Ordering::SeqCst),
+            io: try!(PollEvented::new(RawFdSource::new(fd.0), handle)),
+            fd: Some(fd),
+        })
+    }
+
+    /// Returns the signal number this stream is listening for.
+    pub fn signum(&self) -> i32 {
+        self.signum
+    }
+}
+
+impl Stream for Signal {
+    type Item = i32;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<i32>, io::Error> {
+        let globals = unsafe { &*GLOBALS };
+        let info = &globals.signals[self.signum as usize];
+
+        // Register ourselves as a waiter *before* looking at the pipe, so if
+        // someone else drains it after this point we'll still get notified.
+        info.waiters.lock().unwrap().insert(self.id, task::park());
+
+        // Drain our end of the self-pipe so we get woken up again on the next
+        // write, waking up everyone else waiting on the signals we read as
+        // their event loops may not have seen the bytes we just consumed.
+        let mut buf = [0; 128];
+        loop {
+            match (&mut self.io).read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => wake_waiters(globals, &buf[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        // The handler bumps the generation before writing to the pipe, so we
+        // can't miss a delivery here.
+        let generation = info.generation.load(Ordering::SeqCst);
+        if generation != self.generation {
+            self.generation = generation;
+            Ok(Async::Ready(Some(self.signum)))
+        } else {
+            Ok(Async::NotReady)
+        }
+    }
+}
+
+fn wake_waiters(globals: &Globals, signals: &[u8]) {
+    let mut seen = [false; MAX_SIGNUM];
+    for &signum in signals {
+        let signum = signum as usize;
+        if signum >= MAX_SIGNUM || seen[signum] {
+            continue
+        }
+        seen[signum] = true;
+        let waiters = globals.signals[signum].waiters.lock().unwrap();
+        for task in waiters.values() {
+            task.unpark();
+        }
+    }
+}
+
+fn globals() -> io::Result<&'static Globals> {
+    let mut err = None;
+    INIT.call_once(|| {
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            err = Some(io::Error::last_os_error());
+            return
+        }
+        for &fd in fds.iter() {
+            if let Err(e) = set_nonblocking_cloexec(fd) {
+                err = Some(e);
+                return
+            }
+        }
+        let globals = Globals {
+            receiver: fds[0],
+            sender: fds[1],
+            signals: (0..MAX_SIGNUM).map(|_| {
+                SignalInfo {
+                    generation: AtomicUsize::new(0),
+                    init: Once::new(),
+                    init_err: UnsafeCell::new(None),
+                    prev: UnsafeCell::new(unsafe { mem::zeroed() }),
+                    waiters: Mutex::new(HashMap::new()),
+                }
+            }).collect(),
+        };
+        unsafe {
+            GLOBALS = Box::into_raw(Box::new(globals));
+        }
+    });
+    if let Some(e) = err {
+        return Err(e)
+    }
+    unsafe {
+        if GLOBALS.is_null() {
+            Err(io::Error::new(io::ErrorKind::Other,
+                               "failed to initialize signal handling"))
+        } else {
+            Ok(&*GLOBALS)
+        }
+    }
+}
+
+fn install(globals: &Globals, signum: i32) -> io::Result<()> {
+    let info = &globals.signals[signum as usize];
+    info.init.call_once(|| unsafe {
+        let mut new: libc::sigaction = mem::zeroed();
+        new.sa_sigaction = handler as *const () as libc::sighandler_t;
+        new.sa_flags = libc::SA_RESTART | libc::SA_SIGINFO | libc::SA_NOCLDSTOP;
+        libc::sigemptyset(&mut new.sa_mask);
+        if libc::sigaction(signum, &new, info.prev.get()) != 0 {
+            *info.init_err.get() = io::Error::last_os_error().raw_os_error();
+        }
+    });
+    match unsafe { *info.init_err.get() } {
+        Some(code) => Err(io::Error::from_raw_os_error(code)),
+        None => Ok(()),
+    }
+}
+
+extern fn handler(signum: libc::c_int,
+                  info: *mut libc::siginfo_t,
+                  ptr: *mut libc::c_void) {
+    type FnSigaction = extern fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void);
+    type FnHandler = extern fn(libc::c_int);
+
+    // The code we interrupted may be about to read `errno`, so make sure we
+    // leave it as we found it however we return.
+    let _errno = ErrnoGuard::new();
+
+    unsafe {
+        let globals = &*GLOBALS;
+        let slot = &globals.signals[signum as usize];
+        slot.generation.fetch_add(1, Ordering::SeqCst);
+
+        // If the pipe is full then there's already a wakeup pending, so it's
+        // fine to ignore errors here.
+        let byte = signum as u8;
+        libc::write(globals.sender, &byte as *const u8 as *const libc::c_void, 1);
+
+        let prev = &*slot.prev.get();
+        let fnptr = prev.sa_sigaction;
+        if fnptr == 0 || fnptr == libc::SIG_DFL || fnptr == libc::SIG_IGN {
+            return
+        }
+        if prev.sa_flags & libc::SA_SIGINFO == 0 {
+            let action = mem::transmute::<usize, FnHandler>(fnptr);
+            action(signum)
+        } else {
+            let action = mem::transmute::<usize, FnSigaction>(fnptr);
+            action(signum, info, ptr)
+        }
+    }
+}
+
+struct ErrnoGuard(libc::c_int);
+
+impl ErrnoGuard {
+    fn new() -> ErrnoGuard {
+        ErrnoGuard(unsafe { *errno_location() })
+    }
+}
+
+impl Drop for ErrnoGuard {
+    fn drop(&mut self) {
+        unsafe {
+            *errno_location() = self.0;
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "dragonfly"))]
+unsafe fn errno_location() -> *mut libc::c_int {
+    libc::__errno_location()
+}
+
+#[cfg(any(target_os = "android", target_os = "netbsd", target_os = "openbsd"))]
+unsafe fn errno_location() -> *mut libc::c_int {
+    libc::__errno()
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+unsafe fn errno_location() -> *mut libc::c_int {
+    libc::__error()
+}
+
+fn set_nonblocking_cloexec(fd: RawFd) -> io::Result<()> {
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+        if flags < 0 ||
+           libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        let flags = libc::fcntl(fd, libc::F_GETFD);
+        if flags < 0 ||
+           libc::fcntl(fd, libc::F_SETFD, flags | libc::FD_CLOEXEC) < 0 {
+            return Err(io::Error::last_os_error())
+        }
+    }
+    Ok(())
+}
+
+impl Drop for Signal {
+    fn drop(&mut self) {
+        // All duplicates of the pipe share one open file description, so
+        // closing ours won't remove it from the event loop's poll set. Instead
+        // explicitly deregister it on the event loop and close it after that.
+        let globals = unsafe { &*GLOBALS };
+        let info = &globals.signals[self.signum as usize];
+        info.waiters.lock().unwrap().remove(&self.id);
+
+        let fd = self.fd.take().unwrap();
+        drop(self.io.remote().send(Message::Run(Box::new(move |lp: &Core| {
+            let inner = lp.inner.borrow();
+            drop(inner.io.deregister(&EventedFd(&fd.0)));
+            drop(fd);
+        }))));
+    }
+}
+
+impl Drop for Fd {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.0);
+        }
+    }
+}
@@ -44,6 +44,17 @@ impl Timeout {
             handle: handle.remote().clone(),
         })
     }
+
+    /// Resets this timeout to fire at the instant `at` instead.
+    ///
+    /// The timeout keeps its existing slot in the event loop rather than
+    /// allocating a new one, which makes this suitable for pushing a deadline
+    /// forward over and over again, e.g. for idle connection timers. If the
+    /// timeout has already fired it is re-armed and the future will once again
+    /// resolve only after `at` has passed.
+    pub fn reset(&mut self, at: Instant) {
+        self.token.reset_timeout(at, &self.handle);
+    }
 }
 
 impl Future for Timeout {
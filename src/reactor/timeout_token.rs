@@ -41,7 +41,22 @@ impl TimeoutToken {
     /// This method will panic if the timeout specified was not created by this
     /// loop handle's `add_timeout` method.
     pub fn update_timeout(&self, handle: &Remote) {
-        handle.send(Message::UpdateTimeout(self.token, task::park()))
+        drop(handle.send(Message::UpdateTimeout(self.token, task::park())));
+    }
+
+    /// Resets a previously added timeout to fire at the instant `at` instead,
+    /// reusing the same slot in the event loop.
+    ///
+    /// If the timeout has already fired it is re-armed, and any task currently
+    /// waiting on it will be notified once the new instant has passed.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if the timeout specified was not created by this
+    /// loop handle's `add_timeout` method.
+    pub fn reset_timeout(&mut self, at: Instant, handle: &Remote) {
+        self.when = at;
+        drop(handle.send(Message::ResetTimeout(self.token, at)));
     }
 
     /// Cancel a previously added timeout.
@@ -52,6 +67,6 @@ impl TimeoutToken {
     /// loop handle's `add_timeout` method.
     pub fn cancel_timeout(&self, handle: &Remote) {
         debug!("cancel timeout {}", self.token);
-        handle.send(Message::CancelTimeout(self.token))
+        drop(handle.send(Message::CancelTimeout(self.token)));
     }
 }
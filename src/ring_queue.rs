@@ -0,0 +1,183 @@
+//! A bounded, array-based multi-producer single-consumer queue.
+//!
+//! Unlike the node-based queue in `mpsc_queue`, pushing onto this queue
+//! doesn't allocate. Each slot of the array carries a sequence number which
+//! tells producers and the consumer whose turn it is to use the slot, so
+//! producers only contend on claiming a position and never on the slots
+//! themselves. The price is a fixed capacity: pushing onto a full queue fails
+//! and hands the value back.
+
+// http://www.1024cores.net/home/lock-free-algorithms
+//                         /queues/bounded-mpmc-queue
+
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use mpsc_queue::PopResult;
+
+struct Slot<T> {
+    // Equal to the position a producer may write to this slot at, or to one
+    // past the position the consumer may read from it at.
+    seq: AtomicUsize,
+    value: UnsafeCell<Option<T>>,
+}
+
+/// The multi-producer single-consumer structure. Like `mpsc_queue::Queue`
+/// this may be shared so long as there's only one popper at a time.
+pub struct Queue<T> {
+    slots: Box<[Slot<T>]>,
+    mask: usize,
+
+    // The next position to push at, shared between producers.
+    head: AtomicUsize,
+
+    // The next position to pop from, only touched by the consumer.
+    tail: UnsafeCell<usize>,
+}
+
+unsafe impl<T: Send> Send for Queue<T> { }
+unsafe impl<T: Send> Sync for Queue<T> { }
+
+impl<T> Queue<T> {
+    /// Creates a new queue which holds up to `capacity` values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` isn't a power of two.
+    pub fn new(capacity: usize) -> Queue<T> {
+        assert!(capacity.is_power_of_two(), "capacity must be a power of two");
+        let slots = (0..capacity).map(|i| {
+            Slot {
+                seq: AtomicUsize::new(i),
+                value: UnsafeCell::new(None),
+            }
+        }).collect::<Vec<_>>();
+        Queue {
+            slots: slots.into_boxed_slice(),
+            mask: capacity - 1,
+            head: AtomicUsize::new(0),
+            tail: UnsafeCell::new(0),
+        }
+    }
+
+    /// Pushes a new value onto this queue, or hands it back if the queue is
+    /// full.
+    pub fn push(&self, t: T) -> Result<(), T> {
+        let mut pos = self.head.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos & self.mask];
+            let seq = slot.seq.load(Ordering::Acquire);
+            let diff = seq.wrapping_sub(pos) as isize;
+            if diff == 0 {
+                match self.head.compare_exchange_weak(pos,
+                                                      pos.wrapping_add(1),
+                                                      Ordering::Relaxed,
+                                                      Ordering::Relaxed) {
+                    Ok(_) => {
+                        unsafe { *slot.value.get() = Some(t); }
+                        slot.seq.store(pos.wrapping_add(1), Ordering::Release);
+                        return Ok(())
+                    }
+                    Err(cur) => pos = cur,
+                }
+            } else if diff < 0 {
+                // The consumer hasn't gotten to this slot yet since we last
+                // went around, so we're full.
+                return Err(t)
+            } else {
+                pos = self.head.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Pops some data from this queue.
+    ///
+    /// As with `mpsc_queue::Queue`, `Inconsistent` is returned if a producer
+    /// has claimed the next slot but not yet finished writing to it.
+    ///
+    /// This function is unsafe because only one thread can call it at a time.
+    pub unsafe fn pop(&self) -> PopResult<T> {
+        let pos = *self.tail.get();
+        let slot = &self.slots[pos & self.mask];
+        let seq = slot.seq.load(Ordering::Acquire);
+        if seq == pos.wrapping_add(1) {
+            let t = (*slot.value.get()).take().unwrap();
+            slot.seq.store(pos.wrapping_add(self.mask + 1), Ordering::Release);
+            *self.tail.get() = pos.wrapping_add(1);
+            return PopResult::Data(t)
+        }
+        if self.head.load(Ordering::Acquire) == pos {
+            PopResult::Empty
+        } else {
+            PopResult::Inconsistent
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use mpsc_queue::PopResult;
+    use super::Queue;
+
+    fn pop<T>(q: &Queue<T>) -> Option<T> {
+        match unsafe { q.pop() } {
+            PopResult::Data(t) => Some(t),
+            PopResult::Empty => None,
+            PopResult::Inconsistent => panic!("inconsistent"),
+        }
+    }
+
+    #[test]
+    fn full_and_wrap_around() {
+        let q = Queue::new(4);
+        for round in 0..3 {
+            for i in 0..4 {
+                assert!(q.push(round * 4 + i).is_ok());
+            }
+            assert_eq!(q.push(100), Err(100));
+            for i in 0..4 {
+                assert_eq!(pop(&q), Some(round * 4 + i));
+            }
+            assert_eq!(pop(&q), None);
+        }
+    }
+
+    #[test]
+    fn many_producers() {
+        let q = Arc::new(Queue::new(64));
+        let threads = (0..4).map(|t| {
+            let q = q.clone();
+            thread::spawn(move || {
+                for i in 0..1000 {
+                    let mut v = (t, i);
+                    while let Err(back) = q.push(v) {
+                        v = back;
+                        thread::yield_now();
+                    }
+                }
+            })
+        }).collect::<Vec<_>>();
+
+        let mut next = [0; 4];
+        let mut received = 0;
+        while received < 4000 {
+            match unsafe { q.pop() } {
+                PopResult::Data((t, i)) => {
+                    // Each producer's values come out in the order pushed.
+                    assert_eq!(next[t], i);
+                    next[t] += 1;
+                    received += 1;
+                }
+                PopResult::Empty |
+                PopResult::Inconsistent => thread::yield_now(),
+            }
+        }
+        for t in threads {
+            t.join().unwrap();
+        }
+        assert_eq!(pop(&q), None);
+    }
+}
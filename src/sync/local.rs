@@ -0,0 +1,462 @@
+//! Single-threaded variants of the primitives in the `sync` module.
+//!
+//! These work just like their counterparts in the parent module, but keep
+//! their state in an `Rc` and a `RefCell` rather than behind an atomic
+//! reference count and a lock. They can't be sent to other threads, which
+//! makes them a good fit for tasks sharing state on a single event loop,
+//! spawned through its `Handle`.
+
+use std::cell::{RefCell, UnsafeCell};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+
+use futures::{Future, Poll, Async};
+
+use super::rwlock::MAX_READERS;
+use super::state::{Permits, Notifications};
+
+/// A single-threaded version of `sync::Semaphore`.
+#[derive(Clone)]
+pub struct Semaphore {
+    permits: Rc<RefCell<Permits>>,
+}
+
+/// A future which resolves to a `SemaphorePermit` once permits are available.
+///
+/// This is created by `Semaphore::acquire` and `Semaphore::acquire_many`.
+/// Dropping it gives up its place in the queue.
+pub struct SemaphoreAcquire {
+    semaphore: Semaphore,
+    wanted: usize,
+    id: Option<usize>,
+}
+
+/// Permits acquired from a `Semaphore`, which are returned to it when this is
+/// dropped.
+pub struct SemaphorePermit {
+    semaphore: Semaphore,
+    count: usize,
+}
+
+impl Semaphore {
+    /// Creates a new semaphore with `permits` permits available.
+    pub fn new(permits: usize) -> Semaphore {
+        Semaphore {
+            permits: Rc::new(RefCell::new(Permits::new(permits))),
+        }
+    }
+
+    /// Returns a future which acquires a single permit.
+    pub fn acquire(&self) -> SemaphoreAcquire {
+        self.acquire_many(1)
+    }
+
+    /// Returns a future which acquires `n` permits at once.
+    pub fn acquire_many(&self, n: usize) -> SemaphoreAcquire {
+        SemaphoreAcquire {
+            semaphore: self.clone(),
+            wanted: n,
+            id: None,
+        }
+    }
+
+    /// Acquires `n` permits if they're available right now and no other task
+    /// is waiting for permits.
+    pub fn try_acquire(&self, n: usize) -> Option<SemaphorePermit> {
+        if self.permits.borrow_mut().try_acquire(n) {
+            Some(SemaphorePermit { semaphore: self.clone(), count: n })
+        } else {
+            None
+        }
+    }
+
+    /// Adds `n` new permits to the semaphore, waking up tasks waiting for
+    /// them.
+    pub fn add_permits(&self, n: usize) {
+        self.permits.borrow_mut().release(n);
+    }
+
+    /// Returns the number of permits available right now.
+    pub fn available_permits(&self) -> usize {
+        self.permits.borrow().available()
+    }
+}
+
+impl fmt::Debug for Semaphore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Semaphore")
+         .field("available_permits", &self.available_permits())
+         .finish()
+    }
+}
+
+impl Future for SemaphoreAcquire {
+    type Item = SemaphorePermit;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<SemaphorePermit, ()> {
+        let res = self.semaphore.permits.borrow_mut()
+                      .poll_acquire(&mut self.id, self.wanted);
+        match res {
+            Async::Ready(()) => {
+                Ok(Async::Ready(SemaphorePermit {
+                    semaphore: self.semaphore.clone(),
+                    count: self.wanted,
+                }))
+            }
+            Async::NotReady => Ok(Async::NotReady),
+        }
+    }
+}
+
+impl Drop for SemaphoreAcquire {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.semaphore.permits.borrow_mut().cancel(id);
+        }
+    }
+}
+
+impl SemaphorePermit {
+    /// Returns the number of permits held.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl Drop for SemaphorePermit {
+    fn drop(&mut self) {
+        if self.count > 0 {
+            self.semaphore.add_permits(self.count);
+        }
+    }
+}
+
+impl fmt::Debug for SemaphorePermit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SemaphorePermit")
+         .field("count", &self.count)
+         .finish()
+    }
+}
+
+/// A single-threaded version of `sync::Mutex`.
+pub struct Mutex<T> {
+    semaphore: Semaphore,
+    data: Rc<UnsafeCell<T>>,
+}
+
+/// A future which resolves to a `MutexGuard` once the lock has been acquired.
+///
+/// This is created by `Mutex::lock`.
+pub struct MutexLock<T> {
+    acquire: SemaphoreAcquire,
+    data: Rc<UnsafeCell<T>>,
+}
+
+/// A guard giving access to the value protected by a `Mutex`, which releases
+/// the lock when dropped.
+pub struct MutexGuard<T> {
+    _permit: SemaphorePermit,
+    data: Rc<UnsafeCell<T>>,
+}
+
+impl<T> Mutex<T> {
+    /// Creates a new unlocked mutex protecting `t`.
+    pub fn new(t: T) -> Mutex<T> {
+        Mutex {
+            semaphore: Semaphore::new(1),
+            data: Rc::new(UnsafeCell::new(t)),
+        }
+    }
+
+    /// Returns a future which acquires the lock.
+    pub fn lock(&self) -> MutexLock<T> {
+        MutexLock {
+            acquire: self.semaphore.acquire(),
+            data: self.data.clone(),
+        }
+    }
+
+    /// Acquires the lock if it's free right now and no other task is waiting
+    /// for it.
+    pub fn try_lock(&self) -> Option<MutexGuard<T>> {
+        self.semaphore.try_acquire(1).map(|permit| {
+            MutexGuard {
+                _permit: permit,
+                data: self.data.clone(),
+            }
+        })
+    }
+}
+
+impl<T> Clone for Mutex<T> {
+    fn clone(&self) -> Mutex<T> {
+        Mutex {
+            semaphore: self.semaphore.clone(),
+            data: self.data.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Mutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Mutex")
+         .field("locked", &(self.semaphore.available_permits() == 0))
+         .finish()
+    }
+}
+
+impl<T> Future for MutexLock<T> {
+    type Item = MutexGuard<T>;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<MutexGuard<T>, ()> {
+        let permit = try_ready!(self.acquire.poll());
+        Ok(Async::Ready(MutexGuard {
+            _permit: permit,
+            data: self.data.clone(),
+        }))
+    }
+}
+
+impl<T> Deref for MutexGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.data.get() }
+    }
+}
+
+impl<T> DerefMut for MutexGuard<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+}
+
+/// A single-threaded version of `sync::RwLock`.
+pub struct RwLock<T> {
+    semaphore: Semaphore,
+    data: Rc<UnsafeCell<T>>,
+}
+
+/// A future which resolves to an `RwLockReadGuard` once the lock has been
+/// acquired for reading.
+///
+/// This is created by `RwLock::read`.
+pub struct RwLockRead<T> {
+    acquire: SemaphoreAcquire,
+    data: Rc<UnsafeCell<T>>,
+}
+
+/// A future which resolves to an `RwLockWriteGuard` once the lock has been
+/// acquired for writing.
+///
+/// This is created by `RwLock::write`.
+pub struct RwLockWrite<T> {
+    acquire: SemaphoreAcquire,
+    data: Rc<UnsafeCell<T>>,
+}
+
+/// A guard giving shared access to the value protected by an `RwLock`.
+pub struct RwLockReadGuard<T> {
+    _permit: SemaphorePermit,
+    data: Rc<UnsafeCell<T>>,
+}
+
+/// A guard giving exclusive access to the value protected by an `RwLock`.
+pub struct RwLockWriteGuard<T> {
+    _permit: SemaphorePermit,
+    data: Rc<UnsafeCell<T>>,
+}
+
+impl<T> RwLock<T> {
+    /// Creates a new unlocked reader-writer lock protecting `t`.
+    pub fn new(t: T) -> RwLock<T> {
+        RwLock {
+            semaphore: Semaphore::new(MAX_READERS),
+            data: Rc::new(UnsafeCell::new(t)),
+        }
+    }
+
+    /// Returns a future which acquires the lock for reading.
+    pub fn read(&self) -> RwLockRead<T> {
+        RwLockRead {
+            acquire: self.semaphore.acquire(),
+            data: self.data.clone(),
+        }
+    }
+
+    /// Returns a future which acquires the lock for writing.
+    pub fn write(&self) -> RwLockWrite<T> {
+        RwLockWrite {
+            acquire: self.semaphore.acquire_many(MAX_READERS),
+            data: self.data.clone(),
+        }
+    }
+
+    /// Acquires the lock for reading if that's possible right now and no
+    /// other task is waiting for it.
+    pub fn try_read(&self) -> Option<RwLockReadGuard<T>> {
+        self.semaphore.try_acquire(1).map(|permit| {
+            RwLockReadGuard {
+                _permit: permit,
+                data: self.data.clone(),
+            }
+        })
+    }
+
+    /// Acquires the lock for writing if it's free right now and no other task
+    /// is waiting for it.
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<T>> {
+        self.semaphore.try_acquire(MAX_READERS).map(|permit| {
+            RwLockWriteGuard {
+                _permit: permit,
+                data: self.data.clone(),
+            }
+        })
+    }
+}
+
+impl<T> Clone for RwLock<T> {
+    fn clone(&self) -> RwLock<T> {
+        RwLock {
+            semaphore: self.semaphore.clone(),
+            data: self.data.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for RwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let held = MAX_READERS - self.semaphore.available_permits();
+        f.debug_struct("RwLock")
+         .field("write_locked", &(held == MAX_READERS))
+         .field("readers", &if held == MAX_READERS { 0 } else { held })
+         .finish()
+    }
+}
+
+impl<T> Future for RwLockRead<T> {
+    type Item = RwLockReadGuard<T>;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<RwLockReadGuard<T>, ()> {
+        let permit = try_ready!(self.acquire.poll());
+        Ok(Async::Ready(RwLockReadGuard {
+            _permit: permit,
+            data: self.data.clone(),
+        }))
+    }
+}
+
+impl<T> Future for RwLockWrite<T> {
+    type Item = RwLockWriteGuard<T>;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<RwLockWriteGuard<T>, ()> {
+        let permit = try_ready!(self.acquire.poll());
+        Ok(Async::Ready(RwLockWriteGuard {
+            _permit: permit,
+            data: self.data.clone(),
+        }))
+    }
+}
+
+impl<T> Deref for RwLockReadGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.data.get() }
+    }
+}
+
+impl<T> Deref for RwLockWriteGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.data.get() }
+    }
+}
+
+impl<T> DerefMut for RwLockWriteGuard<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+}
+
+/// A single-threaded version of `sync::Notify`.
+#[derive(Clone)]
+pub struct Notify {
+    inner: Rc<RefCell<Notifications>>,
+}
+
+/// A future which resolves once the `Notify` it came from is notified.
+///
+/// This is created by `Notify::notified`. It only starts waiting once it's
+/// first polled, and dropping it gives up its place in the queue.
+pub struct Notified {
+    notify: Notify,
+    id: Option<usize>,
+}
+
+impl Notify {
+    /// Creates a new `Notify` with nobody waiting on it.
+    pub fn new() -> Notify {
+        Notify {
+            inner: Rc::new(RefCell::new(Notifications::new())),
+        }
+    }
+
+    /// Returns a future which resolves once this `Notify` is notified.
+    pub fn notified(&self) -> Notified {
+        Notified {
+            notify: self.clone(),
+            id: None,
+        }
+    }
+
+    /// Wakes up the task which has been waiting the longest, or lets the
+    /// next `Notified` future to be polled resolve right away.
+    pub fn notify_one(&self) {
+        self.inner.borrow_mut().notify_one();
+    }
+
+    /// Wakes up all tasks currently waiting.
+    pub fn notify_all(&self) {
+        self.inner.borrow_mut().notify_all();
+    }
+}
+
+impl Default for Notify {
+    fn default() -> Notify {
+        Notify::new()
+    }
+}
+
+impl fmt::Debug for Notify {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Notify").finish()
+    }
+}
+
+impl Future for Notified {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        match self.notify.inner.borrow_mut().poll_notified(&mut self.id) {
+            Async::Ready(()) => Ok(Async::Ready(())),
+            Async::NotReady => Ok(Async::NotReady),
+        }
+    }
+}
+
+impl Drop for Notified {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.notify.inner.borrow_mut().cancel(id);
+        }
+    }
+}
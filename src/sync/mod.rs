@@ -0,0 +1,25 @@
+//! Synchronization primitives for tasks.
+//!
+//! The types in this module are the asynchronous counterparts of the ones in
+//! `std::sync`: rather than blocking the thread, a task waiting for a lock,
+//! a permit or a notification is parked with `futures::task::park` and woken
+//! up once it can make progress. Waiting tasks are always served in the order
+//! they started waiting.
+//!
+//! The types at the top level of this module can be shared between threads,
+//! for example between the event loops of a `CorePool`. The `local` module
+//! contains cheaper versions of them for tasks which all live on the same
+//! event loop.
+
+mod mutex;
+mod notify;
+mod rwlock;
+mod semaphore;
+mod state;
+pub mod local;
+
+pub use self::mutex::{Mutex, MutexLock, MutexGuard};
+pub use self::notify::{Notify, Notified};
+pub use self::rwlock::{RwLock, RwLockRead, RwLockWrite};
+pub use self::rwlock::{RwLockReadGuard, RwLockWriteGuard};
+pub use self::semaphore::{Semaphore, SemaphoreAcquire, SemaphorePermit};
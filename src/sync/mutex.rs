@@ -0,0 +1,120 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+use futures::{Future, Poll, Async};
+
+use super::{Semaphore, SemaphoreAcquire, SemaphorePermit};
+
+/// A mutual exclusion lock which parks tasks waiting for it instead of
+/// blocking the thread.
+///
+/// The lock is acquired through the `lock` future, and released when the
+/// resulting `MutexGuard` is dropped. Tasks waiting for the lock get it in the
+/// order they started waiting.
+///
+/// A `Mutex` is a handle which can be `clone`d and sent to other threads, as
+/// long as `T: Send`. All clones protect the same value, and guards keep it
+/// alive on their own, so they can be moved into futures freely. For use on a
+/// single event loop see `local::Mutex`.
+pub struct Mutex<T> {
+    semaphore: Semaphore,
+    data: Arc<UnsafeCell<T>>,
+}
+
+/// A future which resolves to a `MutexGuard` once the lock has been acquired.
+///
+/// This is created by `Mutex::lock`. Dropping it gives up its place in the
+/// queue.
+pub struct MutexLock<T> {
+    acquire: SemaphoreAcquire,
+    data: Arc<UnsafeCell<T>>,
+}
+
+/// A guard giving access to the value protected by a `Mutex`, which releases
+/// the lock when dropped.
+pub struct MutexGuard<T> {
+    _permit: SemaphorePermit,
+    data: Arc<UnsafeCell<T>>,
+}
+
+unsafe impl<T: Send> Send for Mutex<T> {}
+unsafe impl<T: Send> Sync for Mutex<T> {}
+unsafe impl<T: Send> Send for MutexLock<T> {}
+unsafe impl<T: Send> Sync for MutexLock<T> {}
+unsafe impl<T: Send> Send for MutexGuard<T> {}
+unsafe impl<T: Send + Sync> Sync for MutexGuard<T> {}
+
+impl<T> Mutex<T> {
+    /// Creates a new unlocked mutex protecting `t`.
+    pub fn new(t: T) -> Mutex<T> {
+        Mutex {
+            semaphore: Semaphore::new(1),
+            data: Arc::new(UnsafeCell::new(t)),
+        }
+    }
+
+    /// Returns a future which acquires the lock.
+    pub fn lock(&self) -> MutexLock<T> {
+        MutexLock {
+            acquire: self.semaphore.acquire(),
+            data: self.data.clone(),
+        }
+    }
+
+    /// Acquires the lock if it's free right now and no other task is waiting
+    /// for it.
+    pub fn try_lock(&self) -> Option<MutexGuard<T>> {
+        self.semaphore.try_acquire(1).map(|permit| {
+            MutexGuard {
+                _permit: permit,
+                data: self.data.clone(),
+            }
+        })
+    }
+}
+
+impl<T> Clone for Mutex<T> {
+    fn clone(&self) -> Mutex<T> {
+        Mutex {
+            semaphore: self.semaphore.clone(),
+            data: self.data.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Mutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Mutex")
+         .field("locked", &(self.semaphore.available_permits() == 0))
+         .finish()
+    }
+}
+
+impl<T> Future for MutexLock<T> {
+    type Item = MutexGuard<T>;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<MutexGuard<T>, ()> {
+        let permit = try_ready!(self.acquire.poll());
+        Ok(Async::Ready(MutexGuard {
+            _permit: permit,
+            data: self.data.clone(),
+        }))
+    }
+}
+
+impl<T> Deref for MutexGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.data.get() }
+    }
+}
+
+impl<T> DerefMut for MutexGuard<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+}
@@ -0,0 +1,92 @@
+use std::fmt;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use futures::{Future, Poll, Async};
+
+use super::state::Notifications;
+
+/// Notifies tasks waiting on it that something happened.
+///
+/// Tasks wait through the `notified` future. `notify_one` wakes up the task
+/// which has been waiting the longest, or if nobody is waiting, lets the next
+/// `notified` future resolve right away. `notify_all` wakes up every task
+/// waiting at the time.
+///
+/// A `Notify` is a cheap handle which can be `clone`d and sent to other
+/// threads. For use on a single event loop see `local::Notify`.
+#[derive(Clone)]
+pub struct Notify {
+    inner: Arc<StdMutex<Notifications>>,
+}
+
+/// A future which resolves once the `Notify` it came from is notified.
+///
+/// This is created by `Notify::notified`. It only starts waiting once it's
+/// first polled, and dropping it gives up its place in the queue.
+pub struct Notified {
+    notify: Notify,
+    id: Option<usize>,
+}
+
+impl Notify {
+    /// Creates a new `Notify` with nobody waiting on it.
+    pub fn new() -> Notify {
+        Notify {
+            inner: Arc::new(StdMutex::new(Notifications::new())),
+        }
+    }
+
+    /// Returns a future which resolves once this `Notify` is notified.
+    pub fn notified(&self) -> Notified {
+        Notified {
+            notify: self.clone(),
+            id: None,
+        }
+    }
+
+    /// Wakes up the task which has been waiting the longest.
+    ///
+    /// If no task is waiting the notification is stored, and consumed by the
+    /// next `Notified` future to be polled. At most one notification is
+    /// stored.
+    pub fn notify_one(&self) {
+        self.inner.lock().unwrap().notify_one();
+    }
+
+    /// Wakes up all tasks currently waiting.
+    pub fn notify_all(&self) {
+        self.inner.lock().unwrap().notify_all();
+    }
+}
+
+impl Default for Notify {
+    fn default() -> Notify {
+        Notify::new()
+    }
+}
+
+impl fmt::Debug for Notify {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Notify").finish()
+    }
+}
+
+impl Future for Notified {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        match self.notify.inner.lock().unwrap().poll_notified(&mut self.id) {
+            Async::Ready(()) => Ok(Async::Ready(())),
+            Async::NotReady => Ok(Async::NotReady),
+        }
+    }
+}
+
+impl Drop for Notified {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.notify.inner.lock().unwrap().cancel(id);
+        }
+    }
+}
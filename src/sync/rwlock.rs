@@ -0,0 +1,183 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+use futures::{Future, Poll, Async};
+
+use super::{Semaphore, SemaphoreAcquire, SemaphorePermit};
+
+// Readers take one permit each and writers take all of them, so this is the
+// most readers which can hold the lock at once.
+pub const MAX_READERS: usize = ::std::usize::MAX >> 3;
+
+/// A reader-writer lock which parks tasks waiting for it instead of blocking
+/// the thread.
+///
+/// Any number of readers can hold the lock at once, or a single writer. Tasks
+/// get the lock in the order they started waiting, so a waiting writer holds
+/// up readers arriving after it and can't be starved.
+///
+/// Like `Mutex`, an `RwLock` is a handle which can be `clone`d and sent to
+/// other threads, and its guards keep the value alive on their own. For use on
+/// a single event loop see `local::RwLock`.
+pub struct RwLock<T> {
+    semaphore: Semaphore,
+    data: Arc<UnsafeCell<T>>,
+}
+
+/// A future which resolves to an `RwLockReadGuard` once the lock has been
+/// acquired for reading.
+///
+/// This is created by `RwLock::read`.
+pub struct RwLockRead<T> {
+    acquire: SemaphoreAcquire,
+    data: Arc<UnsafeCell<T>>,
+}
+
+/// A future which resolves to an `RwLockWriteGuard` once the lock has been
+/// acquired for writing.
+///
+/// This is created by `RwLock::write`.
+pub struct RwLockWrite<T> {
+    acquire: SemaphoreAcquire,
+    data: Arc<UnsafeCell<T>>,
+}
+
+/// A guard giving shared access to the value protected by an `RwLock`.
+pub struct RwLockReadGuard<T> {
+    _permit: SemaphorePermit,
+    data: Arc<UnsafeCell<T>>,
+}
+
+/// A guard giving exclusive access to the value protected by an `RwLock`.
+pub struct RwLockWriteGuard<T> {
+    _permit: SemaphorePermit,
+    data: Arc<UnsafeCell<T>>,
+}
+
+unsafe impl<T: Send + Sync> Send for RwLock<T> {}
+unsafe impl<T: Send + Sync> Sync for RwLock<T> {}
+unsafe impl<T: Send + Sync> Send for RwLockRead<T> {}
+unsafe impl<T: Send + Sync> Sync for RwLockRead<T> {}
+unsafe impl<T: Send + Sync> Send for RwLockWrite<T> {}
+unsafe impl<T: Send + Sync> Sync for RwLockWrite<T> {}
+unsafe impl<T: Send + Sync> Send for RwLockReadGuard<T> {}
+unsafe impl<T: Send + Sync> Sync for RwLockReadGuard<T> {}
+unsafe impl<T: Send + Sync> Send for RwLockWriteGuard<T> {}
+unsafe impl<T: Send + Sync> Sync for RwLockWriteGuard<T> {}
+
+impl<T> RwLock<T> {
+    /// Creates a new unlocked reader-writer lock protecting `t`.
+    pub fn new(t: T) -> RwLock<T> {
+        RwLock {
+            semaphore: Semaphore::new(MAX_READERS),
+            data: Arc::new(UnsafeCell::new(t)),
+        }
+    }
+
+    /// Returns a future which acquires the lock for reading.
+    pub fn read(&self) -> RwLockRead<T> {
+        RwLockRead {
+            acquire: self.semaphore.acquire(),
+            data: self.data.clone(),
+        }
+    }
+
+    /// Returns a future which acquires the lock for writing.
+    pub fn write(&self) -> RwLockWrite<T> {
+        RwLockWrite {
+            acquire: self.semaphore.acquire_many(MAX_READERS),
+            data: self.data.clone(),
+        }
+    }
+
+    /// Acquires the lock for reading if that's possible right now and no
+    /// other task is waiting for it.
+    pub fn try_read(&self) -> Option<RwLockReadGuard<T>> {
+        self.semaphore.try_acquire(1).map(|permit| {
+            RwLockReadGuard {
+                _permit: permit,
+                data: self.data.clone(),
+            }
+        })
+    }
+
+    /// Acquires the lock for writing if it's free right now and no other task
+    /// is waiting for it.
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<T>> {
+        self.semaphore.try_acquire(MAX_READERS).map(|permit| {
+            RwLockWriteGuard {
+                _permit: permit,
+                data: self.data.clone(),
+            }
+        })
+    }
+}
+
+impl<T> Clone for RwLock<T> {
+    fn clone(&self) -> RwLock<T> {
+        RwLock {
+            semaphore: self.semaphore.clone(),
+            data: self.data.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for RwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let held = MAX_READERS - self.semaphore.available_permits();
+        f.debug_struct("RwLock")
+         .field("write_locked", &(held == MAX_READERS))
+         .field("readers", &if held == MAX_READERS { 0 } else { held })
+         .finish()
+    }
+}
+
+impl<T> Future for RwLockRead<T> {
+    type Item = RwLockReadGuard<T>;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<RwLockReadGuard<T>, ()> {
+        let permit = try_ready!(self.acquire.poll());
+        Ok(Async::Ready(RwLockReadGuard {
+            _permit: permit,
+            data: self.data.clone(),
+        }))
+    }
+}
+
+impl<T> Future for RwLockWrite<T> {
+    type Item = RwLockWriteGuard<T>;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<RwLockWriteGuard<T>, ()> {
+        let permit = try_ready!(self.acquire.poll());
+        Ok(Async::Ready(RwLockWriteGuard {
+            _permit: permit,
+            data: self.data.clone(),
+        }))
+    }
+}
+
+impl<T> Deref for RwLockReadGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.data.get() }
+    }
+}
+
+impl<T> Deref for RwLockWriteGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.data.get() }
+    }
+}
+
+impl<T> DerefMut for RwLockWriteGuard<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+}
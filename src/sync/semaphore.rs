@@ -0,0 +1,143 @@
+use std::fmt;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use futures::{Future, Poll, Async};
+
+use super::state::Permits;
+
+/// A semaphore handing out a fixed number of permits to tasks.
+///
+/// Tasks acquire permits through the `acquire` and `acquire_many` futures,
+/// and give them back by dropping the resulting `SemaphorePermit`. Tasks
+/// waiting for permits are served in the order they started waiting, so a
+/// task asking for many permits isn't starved by others asking for few.
+///
+/// A `Semaphore` is a cheap handle which can be `clone`d and sent to other
+/// threads; all clones share the same permits. For use on a single event
+/// loop see `local::Semaphore`.
+#[derive(Clone)]
+pub struct Semaphore {
+    permits: Arc<StdMutex<Permits>>,
+}
+
+/// A future which resolves to a `SemaphorePermit` once permits are available.
+///
+/// This is created by `Semaphore::acquire` and `Semaphore::acquire_many`.
+/// Dropping it gives up its place in the queue.
+pub struct SemaphoreAcquire {
+    semaphore: Semaphore,
+    wanted: usize,
+    id: Option<usize>,
+}
+
+/// Permits acquired from a `Semaphore`, which are returned to it when this is
+/// dropped.
+pub struct SemaphorePermit {
+    semaphore: Semaphore,
+    count: usize,
+}
+
+impl Semaphore {
+    /// Creates a new semaphore with `permits` permits available.
+    pub fn new(permits: usize) -> Semaphore {
+        Semaphore {
+            permits: Arc::new(StdMutex::new(Permits::new(permits))),
+        }
+    }
+
+    /// Returns a future which acquires a single permit.
+    pub fn acquire(&self) -> SemaphoreAcquire {
+        self.acquire_many(1)
+    }
+
+    /// Returns a future which acquires `n` permits at once.
+    ///
+    /// Note that if `n` is more than the semaphore will ever have available
+    /// the future never resolves, and holds up all tasks queued behind it.
+    pub fn acquire_many(&self, n: usize) -> SemaphoreAcquire {
+        SemaphoreAcquire {
+            semaphore: self.clone(),
+            wanted: n,
+            id: None,
+        }
+    }
+
+    /// Acquires `n` permits if they're available right now and no other task
+    /// is waiting for permits.
+    pub fn try_acquire(&self, n: usize) -> Option<SemaphorePermit> {
+        if self.permits.lock().unwrap().try_acquire(n) {
+            Some(SemaphorePermit { semaphore: self.clone(), count: n })
+        } else {
+            None
+        }
+    }
+
+    /// Adds `n` new permits to the semaphore, waking up tasks waiting for
+    /// them.
+    pub fn add_permits(&self, n: usize) {
+        self.permits.lock().unwrap().release(n);
+    }
+
+    /// Returns the number of permits available right now.
+    pub fn available_permits(&self) -> usize {
+        self.permits.lock().unwrap().available()
+    }
+}
+
+impl fmt::Debug for Semaphore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Semaphore")
+         .field("available_permits", &self.available_permits())
+         .finish()
+    }
+}
+
+impl Future for SemaphoreAcquire {
+    type Item = SemaphorePermit;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<SemaphorePermit, ()> {
+        let res = self.semaphore.permits.lock().unwrap()
+                      .poll_acquire(&mut self.id, self.wanted);
+        match res {
+            Async::Ready(()) => {
+                Ok(Async::Ready(SemaphorePermit {
+                    semaphore: self.semaphore.clone(),
+                    count: self.wanted,
+                }))
+            }
+            Async::NotReady => Ok(Async::NotReady),
+        }
+    }
+}
+
+impl Drop for SemaphoreAcquire {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.semaphore.permits.lock().unwrap().cancel(id);
+        }
+    }
+}
+
+impl SemaphorePermit {
+    /// Returns the number of permits held.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl Drop for SemaphorePermit {
+    fn drop(&mut self) {
+        if self.count > 0 {
+            self.semaphore.add_permits(self.count);
+        }
+    }
+}
+
+impl fmt::Debug for SemaphorePermit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SemaphorePermit")
+         .field("count", &self.count)
+         .finish()
+    }
+}
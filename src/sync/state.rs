@@ -0,0 +1,188 @@
+//! The queues of waiting tasks behind the primitives in this module.
+//!
+//! These contain no locking of their own: the `Send` primitives keep them in
+//! a `std::sync::Mutex` and the local ones in a `RefCell`.
+
+use std::collections::{HashMap, VecDeque};
+
+use futures::Async;
+use futures::task::{self, Task};
+
+/// A pool of permits handed out to waiters in FIFO order.
+pub struct Permits {
+    available: usize,
+    next_id: usize,
+    waiters: VecDeque<Waiter>,
+
+    // Waiters which have been handed their permits, but haven't been polled
+    // since to pick them up, along with how many permits they were given.
+    granted: HashMap<usize, usize>,
+}
+
+struct Waiter {
+    id: usize,
+    wanted: usize,
+    task: Task,
+}
+
+impl Permits {
+    pub fn new(available: usize) -> Permits {
+        Permits {
+            available: available,
+            next_id: 0,
+            waiters: VecDeque::new(),
+            granted: HashMap::new(),
+        }
+    }
+
+    pub fn available(&self) -> usize {
+        self.available
+    }
+
+    /// Takes `wanted` permits if they're available and nobody is queued up
+    /// in front of us.
+    pub fn try_acquire(&mut self, wanted: usize) -> bool {
+        if self.waiters.is_empty() && self.available >= wanted {
+            self.available -= wanted;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Attempts to acquire `wanted` permits for the current task.
+    ///
+    /// If the permits aren't available the task is queued up and parked, and
+    /// its place in the queue is stored in `id` so that the next call picks
+    /// up where this one left off.
+    pub fn poll_acquire(&mut self, id: &mut Option<usize>, wanted: usize)
+                        -> Async<()> {
+        if let Some(i) = *id {
+            if self.granted.remove(&i).is_some() {
+                *id = None;
+                return Async::Ready(())
+            }
+            if let Some(waiter) = self.waiters.iter_mut().find(|w| w.id == i) {
+                waiter.task = task::park();
+            }
+            return Async::NotReady
+        }
+        if self.try_acquire(wanted) {
+            return Async::Ready(())
+        }
+        let i = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.waiters.push_back(Waiter {
+            id: i,
+            wanted: wanted,
+            task: task::park(),
+        });
+        *id = Some(i);
+        Async::NotReady
+    }
+
+    /// Gives up the place in the queue `id`, returning any permits it was
+    /// already handed.
+    pub fn cancel(&mut self, id: usize) {
+        if let Some(n) = self.granted.remove(&id) {
+            self.release(n);
+        } else if let Some(pos) = self.waiters.iter().position(|w| w.id == id) {
+            self.waiters.remove(pos);
+
+            // We may have been holding up the waiters behind us.
+            self.grant();
+        }
+    }
+
+    pub fn release(&mut self, n: usize) {
+        self.available += n;
+        self.grant();
+    }
+
+    // Hands out permits to the front of the queue for as long as there are
+    // enough of them.
+    fn grant(&mut self) {
+        loop {
+            match self.waiters.front() {
+                Some(w) if w.wanted <= self.available => {}
+                _ => break,
+            }
+            let waiter = self.waiters.pop_front().unwrap();
+            self.available -= waiter.wanted;
+            self.granted.insert(waiter.id, waiter.wanted);
+            waiter.task.unpark();
+        }
+    }
+}
+
+/// The tasks waiting on a `Notify`.
+pub struct Notifications {
+    next_id: usize,
+    waiters: VecDeque<(usize, Task)>,
+
+    // Waiters which have been notified, and whether it was by `notify_one`.
+    notified: HashMap<usize, bool>,
+
+    // Set by `notify_one` if there was nobody waiting.
+    permit: bool,
+}
+
+impl Notifications {
+    pub fn new() -> Notifications {
+        Notifications {
+            next_id: 0,
+            waiters: VecDeque::new(),
+            notified: HashMap::new(),
+            permit: false,
+        }
+    }
+
+    pub fn poll_notified(&mut self, id: &mut Option<usize>) -> Async<()> {
+        if let Some(i) = *id {
+            if self.notified.remove(&i).is_some() {
+                *id = None;
+                return Async::Ready(())
+            }
+            if let Some(w) = self.waiters.iter_mut().find(|w| w.0 == i) {
+                w.1 = task::park();
+            }
+            return Async::NotReady
+        }
+        if self.permit {
+            self.permit = false;
+            return Async::Ready(())
+        }
+        let i = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.waiters.push_back((i, task::park()));
+        *id = Some(i);
+        Async::NotReady
+    }
+
+    pub fn notify_one(&mut self) {
+        match self.waiters.pop_front() {
+            Some((i, task)) => {
+                self.notified.insert(i, true);
+                task.unpark();
+            }
+            None => self.permit = true,
+        }
+    }
+
+    pub fn notify_all(&mut self) {
+        for (i, task) in self.waiters.drain(..) {
+            self.notified.insert(i, false);
+            task.unpark();
+        }
+    }
+
+    /// Stops waiting for a notification. If `notify_one` already picked this
+    /// waiter it's passed on to the next one, so it isn't lost.
+    pub fn cancel(&mut self, id: usize) {
+        match self.notified.remove(&id) {
+            Some(true) => self.notify_one(),
+            Some(false) => {}
+            None => self.waiters.retain(|w| w.0 != id),
+        }
+    }
+}
@@ -0,0 +1,383 @@
+//! A hashed hierarchical timing wheel
+//!
+//! This wheel is used to manage timer state in the event loop. All timeouts go
+//! into this wheel and we also cancel timeouts from this wheel. Unlike a binary
+//! heap, insertion and cancellation are both O(1) which matters when a server
+//! keeps around a large number of mostly idle per-connection timers.
+//!
+//! Time is divided into ticks of a configurable resolution. The wheel has a
+//! number of levels, each of which has `SLOTS` slots. A slot on level `n`
+//! covers `SLOTS^n` ticks, and when the wheel's current tick reaches the
+//! beginning of a slot on a higher level all entries in that slot are
+//! "cascaded" down into lower levels. Entries which are further out than the
+//! top level can represent are placed in the top level and re-inserted every
+//! time that slot comes around.
+//!
+//! Timeouts are always rounded *up* to the next tick, so an entry will never be
+//! returned from `poll` before the instant it was inserted with.
+
+use std::time::{Duration, Instant};
+
+use slab::Slab;
+
+const LEVEL_BITS: usize = 6;
+const SLOTS: usize = 1 << LEVEL_BITS;
+const LEVELS: usize = 4;
+
+// Index into `heads` for the list of entries which have expired but which
+// haven't yet been returned from `poll`.
+const READY: usize = SLOTS * LEVELS;
+
+pub struct Wheel<T> {
+    // The instant corresponding to tick 0 and the duration of each tick.
+    start: Instant,
+    resolution: Duration,
+
+    // The last tick that has been processed. All entries whose deadline is at
+    // or before this tick are on the `READY` list.
+    tick: u64,
+
+    // Heads of the doubly linked lists of entries for each slot, followed by
+    // the head of the ready list.
+    heads: Vec<Option<usize>>,
+
+    // A bitmask per level of which slots are non-empty, used to quickly find
+    // the next tick at which there's something to do.
+    occupied: [u64; LEVELS],
+
+    entries: Slab<Entry<T>>,
+}
+
+pub struct Slot {
+    idx: usize,
+}
+
+struct Entry<T> {
+    when: u64,
+    list: usize,
+    prev: Option<usize>,
+    next: Option<usize>,
+    value: T,
+}
+
+impl<T> Wheel<T> {
+    pub fn new(resolution: Duration) -> Wheel<T> {
+        assert!(resolution > Duration::new(0, 0),
+                "timer resolution must be nonzero");
+        Wheel {
+            start: Instant::now(),
+            resolution: resolution,
+            tick: 0,
+            heads: vec![None; READY + 1],
+            occupied: [0; LEVELS],
+            entries: Slab::with_capacity(128),
+        }
+    }
+
+    /// Inserts a new value into this wheel which will be returned from `poll`
+    /// once `at` has passed.
+    ///
+    /// The slot returned can later get passed to `remove` to remove the value
+    /// from the wheel, but only if the value was previously not returned from
+    /// `poll`.
+    pub fn insert(&mut self, at: Instant, t: T) -> Slot {
+        let when = self.tick_ceil(at);
+        if self.entries.available() == 0 {
+            let len = self.entries.len();
+            self.entries.reserve_exact(len);
+        }
+        let idx = self.entries.insert(Entry {
+            when: when,
+            list: READY,
+            prev: None,
+            next: None,
+            value: t,
+        }).ok().unwrap();
+        self.link(idx);
+        Slot { idx: idx }
+    }
+
+    /// Removes a previously inserted value from this wheel.
+    pub fn remove(&mut self, slot: Slot) -> T {
+        self.unlink(slot.idx);
+        self.entries.remove(slot.idx).unwrap().value
+    }
+
+    /// Returns the earliest instant at which `poll` may have something to do,
+    /// or `None` if the wheel is empty.
+    ///
+    /// The instant returned may be earlier than the next entry's deadline as
+    /// the wheel may need to be turned to cascade entries from higher levels.
+    pub fn next_timeout(&self) -> Option<Instant> {
+        if self.heads[READY].is_some() {
+            return Some(self.instant(self.tick))
+        }
+        self.next_tick().map(|tick| self.instant(tick))
+    }
+
+    /// Advances the wheel to `now`, returning a value whose deadline has
+    /// passed if there is one.
+    pub fn poll(&mut self, now: Instant) -> Option<T> {
+        let target = self.tick_floor(now);
+        while self.heads[READY].is_none() {
+            match self.next_tick() {
+                Some(tick) if tick <= target => self.process(tick),
+
+                // Nothing else to do before `target`, so we can skip straight
+                // there without visiting all the empty slots in between.
+                _ => {
+                    if target > self.tick {
+                        self.tick = target;
+                    }
+                    break
+                }
+            }
+        }
+
+        let idx = match self.heads[READY] {
+            Some(idx) => idx,
+            None => return None,
+        };
+        self.unlink(idx);
+        Some(self.entries.remove(idx).unwrap().value)
+    }
+
+    fn process(&mut self, tick: u64) {
+        debug_assert!(tick > self.tick);
+        self.tick = tick;
+
+        // Cascade all higher levels whose slot boundary we just reached, from
+        // the top down so entries can trickle all the way to the ready list.
+        for level in (1..LEVELS).rev() {
+            let shift = LEVEL_BITS * level;
+            if tick & ((1 << shift) - 1) != 0 {
+                continue
+            }
+            let slot = ((tick >> shift) as usize) & (SLOTS - 1);
+            self.relink_all(level * SLOTS + slot);
+        }
+
+        let slot = (tick as usize) & (SLOTS - 1);
+        self.relink_all(slot);
+    }
+
+    // Takes all entries out of the list `list` and re-inserts them relative to
+    // the current tick.
+    fn relink_all(&mut self, list: usize) {
+        let mut cur = self.heads[list].take();
+        if list < READY {
+            self.occupied[list / SLOTS] &= !(1 << (list % SLOTS));
+        }
+        while let Some(idx) = cur {
+            cur = self.entries[idx].next;
+            self.link(idx);
+        }
+    }
+
+    // Returns the next tick after the current one at which a slot with entries
+    // in it will be processed.
+    fn next_tick(&self) -> Option<u64> {
+        let mut ret = None;
+        for level in 0..LEVELS {
+            if self.occupied[level] == 0 {
+                continue
+            }
+            let shift = LEVEL_BITS * level;
+            let base = self.tick >> shift;
+            for i in 1..SLOTS as u64 + 1 {
+                let slot = ((base + i) as usize) & (SLOTS - 1);
+                if self.occupied[level] & (1 << slot) != 0 {
+                    let tick = (base + i) << shift;
+                    if ret.map(|t| tick < t).unwrap_or(true) {
+                        ret = Some(tick);
+                    }
+                    break
+                }
+            }
+        }
+        ret
+    }
+
+    fn link(&mut self, idx: usize) {
+        let when = self.entries[idx].when;
+        let list = if when <= self.tick {
+            READY
+        } else {
+            let delta = when - self.tick;
+            let mut level = 0;
+            while level < LEVELS - 1 && delta >= 1 << (LEVEL_BITS * (level + 1)) {
+                level += 1;
+            }
+            let slot = ((when >> (LEVEL_BITS * level)) as usize) & (SLOTS - 1);
+            self.occupied[level] |= 1 << slot;
+            level * SLOTS + slot
+        };
+
+        let head = self.heads[list];
+        if let Some(head) = head {
+            self.entries[head].prev = Some(idx);
+        }
+        let entry = &mut self.entries[idx];
+        entry.list = list;
+        entry.prev = None;
+        entry.next = head;
+        self.heads[list] = Some(idx);
+    }
+
+    fn unlink(&mut self, idx: usize) {
+        let (list, prev, next) = {
+            let entry = &self.entries[idx];
+            (entry.list, entry.prev, entry.next)
+        };
+        match prev {
+            Some(prev) => self.entries[prev].next = next,
+            None => self.heads[list] = next,
+        }
+        if let Some(next) = next {
+            self.entries[next].prev = prev;
+        }
+        if list < READY && self.heads[list].is_none() {
+            self.occupied[list / SLOTS] &= !(1 << (list % SLOTS));
+        }
+    }
+
+    fn tick_floor(&self, at: Instant) -> u64 {
+        if at <= self.start {
+            return 0
+        }
+        nanos(at - self.start) / nanos(self.resolution)
+    }
+
+    fn tick_ceil(&self, at: Instant) -> u64 {
+        if at <= self.start {
+            return 0
+        }
+        let res = nanos(self.resolution);
+        (nanos(at - self.start) + res - 1) / res
+    }
+
+    fn instant(&self, tick: u64) -> Instant {
+        let nanos = nanos(self.resolution).saturating_mul(tick);
+        self.start + Duration::new(nanos / 1_000_000_000,
+                                   (nanos % 1_000_000_000) as u32)
+    }
+}
+
+fn nanos(dur: Duration) -> u64 {
+    dur.as_secs()
+       .saturating_mul(1_000_000_000)
+       .saturating_add(dur.subsec_nanos() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::Wheel;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    fn drain(w: &mut Wheel<u32>, now: Instant) -> Vec<u32> {
+        let mut v = Vec::new();
+        while let Some(i) = w.poll(now) {
+            v.push(i);
+        }
+        v.sort();
+        v
+    }
+
+    #[test]
+    fn simple() {
+        let mut w = Wheel::new(ms(1));
+        let start = w.start;
+        w.insert(start + ms(10), 1);
+        w.insert(start + ms(5), 2);
+        assert_eq!(drain(&mut w, start + ms(4)), Vec::<u32>::new());
+        assert_eq!(drain(&mut w, start + ms(5)), vec![2]);
+        assert_eq!(drain(&mut w, start + ms(9)), Vec::<u32>::new());
+        assert_eq!(drain(&mut w, start + ms(10)), vec![1]);
+        assert!(w.poll(start + ms(100)).is_none());
+        assert!(w.next_timeout().is_none());
+    }
+
+    #[test]
+    fn never_early() {
+        let mut w = Wheel::new(ms(10));
+        let start = w.start;
+        w.insert(start + ms(11), 1);
+        assert!(w.poll(start + ms(19)).is_none());
+        assert_eq!(w.next_timeout(), Some(start + ms(20)));
+        assert_eq!(w.poll(start + ms(20)), Some(1));
+    }
+
+    #[test]
+    fn cascade() {
+        let mut w = Wheel::new(ms(1));
+        let start = w.start;
+        let deadlines = [1, 63, 64, 65, 100, 4095, 4096, 4097, 300_000,
+                         20_000_000, 40_000_000];
+        for (i, &d) in deadlines.iter().enumerate() {
+            w.insert(start + ms(d), i as u32);
+        }
+        for (i, &d) in deadlines.iter().enumerate() {
+            assert_eq!(drain(&mut w, start + ms(d - 1)), Vec::<u32>::new(),
+                       "fired early: {}", d);
+            assert_eq!(drain(&mut w, start + ms(d)), vec![i as u32],
+                       "didn't fire: {}", d);
+        }
+        assert!(w.next_timeout().is_none());
+    }
+
+    #[test]
+    fn big_jump() {
+        let mut w = Wheel::new(ms(1));
+        let start = w.start;
+        w.insert(start + ms(3), 1);
+        w.insert(start + ms(70), 2);
+        w.insert(start + ms(5000), 3);
+        assert_eq!(drain(&mut w, start + ms(10_000)), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn remove() {
+        let mut w = Wheel::new(ms(1));
+        let start = w.start;
+        w.insert(start + ms(5), 1);
+        let two = w.insert(start + ms(5), 2);
+        let three = w.insert(start + ms(500), 3);
+        w.insert(start + ms(7), 4);
+        assert_eq!(w.remove(two), 2);
+        assert_eq!(w.remove(three), 3);
+        assert_eq!(drain(&mut w, start + ms(1000)), vec![1, 4]);
+    }
+
+    #[test]
+    fn remove_ready() {
+        let mut w = Wheel::new(ms(1));
+        let start = w.start;
+        let one = w.insert(start + ms(5), 1);
+        let two = w.insert(start + ms(5), 2);
+        assert_eq!(w.next_timeout(), Some(start + ms(5)));
+        let (first, rest) = match w.poll(start + ms(5)) {
+            Some(1) => (1, two),
+            Some(2) => (2, one),
+            other => panic!("unexpected: {:?}", other),
+        };
+        assert_eq!(w.next_timeout(), Some(start + ms(5)));
+        assert_eq!(w.remove(rest), 3 - first);
+        assert!(w.poll(start + ms(5)).is_none());
+        assert!(w.next_timeout().is_none());
+    }
+
+    #[test]
+    fn insert_in_past() {
+        let mut w = Wheel::new(ms(1));
+        let start = w.start;
+        assert!(w.poll(start + ms(100)).is_none());
+        w.insert(start + ms(50), 1);
+        assert_eq!(w.next_timeout(), Some(start + ms(100)));
+        assert_eq!(w.poll(start + ms(100)), Some(1));
+    }
+}
@@ -0,0 +1,156 @@
+extern crate env_logger;
+extern crate futures;
+extern crate tokio_core;
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use futures::Future;
+use tokio_core::net::UdpSocket;
+use tokio_core::reactor::{Builder, Core, JoinError, Timeout};
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+#[test]
+fn defaults() {
+    drop(env_logger::init());
+    let core = t!(Core::new());
+    assert_eq!(core.name(), None);
+
+    let core = t!(Builder::new().name("named").build());
+    assert_eq!(core.name(), Some("named"));
+}
+
+#[test]
+fn slabs_grow_from_zero() {
+    drop(env_logger::init());
+    let mut l = t!(Builder::new().slab_capacity(0).build());
+    let handle = l.handle();
+
+    let hits = Rc::new(Cell::new(0));
+    for _ in 0..10 {
+        let hits = hits.clone();
+        let timeout = t!(Timeout::new(Duration::from_millis(1), &handle));
+        handle.spawn(timeout.then(move |_| {
+            hits.set(hits.get() + 1);
+            Ok(())
+        }));
+    }
+    let sockets = (0..10).map(|_| {
+        t!(UdpSocket::bind(&t!("127.0.0.1:0".parse()), &handle))
+    }).collect::<Vec<_>>();
+
+    t!(l.run(Timeout::new(Duration::from_millis(50), &handle).unwrap()));
+    assert_eq!(hits.get(), 10);
+    drop(sockets);
+}
+
+#[test]
+fn max_io_sources() {
+    drop(env_logger::init());
+    let l = t!(Builder::new().slab_capacity(1).max_io_sources(2).build());
+    let handle = l.handle();
+    let addr = t!("127.0.0.1:0".parse());
+
+    let a = t!(UdpSocket::bind(&addr, &handle));
+    let b = t!(UdpSocket::bind(&addr, &handle));
+    assert!(UdpSocket::bind(&addr, &handle).is_err());
+
+    // Dropping a source makes room for another.
+    drop(a);
+    let mut l = l;
+    l.turn(Some(Duration::new(0, 0)));
+    t!(UdpSocket::bind(&addr, &handle));
+    drop(b);
+}
+
+#[test]
+fn max_timeouts() {
+    drop(env_logger::init());
+    let l = t!(Builder::new().max_timeouts(1).build());
+    let handle = l.handle();
+
+    let _a = t!(Timeout::new(Duration::from_secs(10), &handle));
+    assert!(Timeout::new(Duration::from_secs(10), &handle).is_err());
+}
+
+#[test]
+fn max_tasks() {
+    drop(env_logger::init());
+    let mut l = t!(Builder::new().task_capacity(1).max_tasks(1).build());
+    let (tx, rx) = futures::oneshot::<()>();
+    l.handle().spawn(rx.map_err(|_| ()));
+
+    // Tasks over the limit are dropped, wherever they're spawned from
+    l.handle().spawn(futures::empty());
+    assert!(l.handle().try_spawn(futures::empty()).is_err());
+    let local = l.handle().spawn_join(futures::empty::<(), ()>());
+    let remote = l.remote().spawn_join(|_| futures::empty::<(), ()>());
+    match t!(l.run(local.then(Ok::<_, ()>))) {
+        Err(JoinError::Dropped) => {}
+        other => panic!("unexpected: {:?}", other),
+    }
+    match t!(l.run(remote.then(Ok::<_, ()>))) {
+        Err(JoinError::Dropped) => {}
+        other => panic!("unexpected: {:?}", other),
+    }
+    assert_eq!(l.task_count(), 1);
+
+    // Once a task completes there's room for another
+    tx.complete(());
+    l.run_until_empty();
+    t!(l.handle().try_spawn(futures::lazy(|| Ok(()))));
+    l.run_until_empty();
+    let task = l.handle().spawn_join(futures::lazy(|| Ok::<_, ()>(3)));
+    assert_eq!(t!(l.run(task)), 3);
+}
+
+#[test]
+fn panic_hook() {
+    drop(env_logger::init());
+    let caught = Rc::new(Cell::new(false));
+    let caught2 = caught.clone();
+    let mut l = t!(Builder::new().panic_hook(move |_, _| caught2.set(true)).build());
+
+    l.handle().spawn(futures::lazy(|| -> Result<(), ()> { panic!("boom") }));
+    l.turn(Some(Duration::new(0, 0)));
+    assert!(caught.get());
+}
+
+#[test]
+fn timer_resolution() {
+    drop(env_logger::init());
+    let mut l = t!(Builder::new()
+                       .timer_resolution(Duration::from_millis(50))
+                       .build());
+    let start = Instant::now();
+    let timeout = t!(Timeout::new(Duration::from_millis(1), &l.handle()));
+    t!(l.run(timeout));
+    assert!(start.elapsed() >= Duration::from_millis(40));
+}
+
+#[test]
+fn turn_budgets() {
+    drop(env_logger::init());
+    let mut l = t!(Builder::new().max_tasks_per_turn(1).build());
+
+    for _ in 0..2 {
+        l.handle().spawn(futures::lazy(|| Ok(())));
+    }
+    assert_eq!(l.turn(None).tasks(), 1);
+    assert_eq!(l.turn(None).tasks(), 1);
+}
+
+#[test]
+fn zero_turn_budgets() {
+    drop(env_logger::init());
+    assert!(Builder::new().max_tasks_per_turn(0).build().is_err());
+    assert!(Builder::new().max_io_per_turn(0).build().is_err());
+    assert!(Builder::new().max_messages_per_turn(0).build().is_err());
+}
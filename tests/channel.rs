@@ -0,0 +1,259 @@
+extern crate env_logger;
+extern crate futures;
+extern crate tokio_core;
+
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+use futures::{Future, Sink, AsyncSink};
+use futures::future;
+use futures::stream::{self, Stream};
+use tokio_core::channel;
+use tokio_core::reactor::Core;
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+#[test]
+fn bounded_fills_up() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let (mut tx, rx) = t!(channel::bounded(2, &l.handle()));
+
+    l.run(future::lazy(|| {
+        assert!(t!(tx.start_send(1)).is_ready());
+        assert!(t!(tx.start_send(2)).is_ready());
+        match t!(tx.start_send(3)) {
+            AsyncSink::NotReady(3) => {}
+            _ => panic!("channel should be full"),
+        }
+        Ok::<_, ()>(())
+    })).unwrap();
+
+    let (first, rx) = match l.run(rx.into_future()) {
+        Ok(pair) => pair,
+        Err(_) => panic!("receiver failed"),
+    };
+    assert_eq!(first, Some(1));
+    assert!(t!(tx.start_send(3)).is_ready());
+    drop(tx);
+    assert_eq!(t!(l.run(rx.collect())), vec![2, 3]);
+}
+
+#[test]
+fn bounded_from_other_thread() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let (tx, rx) = t!(channel::bounded(4, &l.handle()));
+
+    let t = thread::spawn(move || {
+        let items = stream::iter((0..100).map(Ok::<_, io::Error>));
+        drop(t!(tx.send_all(items).wait()));
+    });
+    let received = t!(l.run(rx.collect()));
+    t.join().unwrap();
+    assert_eq!(received, (0..100).collect::<Vec<_>>());
+}
+
+#[test]
+fn bounded_many_senders() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let (tx, rx) = t!(channel::bounded(1, &l.handle()));
+
+    let threads = (0..8).map(|i| {
+        let tx = tx.clone();
+        thread::spawn(move || {
+            let items = (0..50).map(move |j| Ok::<_, io::Error>(i * 50 + j));
+            let items = stream::iter(items);
+            drop(t!(tx.send_all(items).wait()));
+        })
+    }).collect::<Vec<_>>();
+    drop(tx);
+    let mut received = t!(l.run(rx.collect()));
+    for t in threads {
+        t.join().unwrap();
+    }
+    received.sort();
+    assert_eq!(received, (0..400).collect::<Vec<_>>());
+}
+
+#[test]
+fn bounded_receiver_dropped() {
+    drop(env_logger::init());
+    let l = t!(Core::new());
+    let (tx, rx) = t!(channel::bounded(1, &l.handle()));
+    let tx = t!(tx.send(1).wait());
+
+    let t = thread::spawn(move || tx.send(2).wait().is_err());
+    thread::sleep(Duration::from_millis(20));
+    drop(rx);
+    assert!(t.join().unwrap());
+}
+
+#[test]
+fn forward_into_sender() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let (tx, rx) = t!(channel::channel(&l.handle()));
+
+    let items = stream::iter((0..10).map(Ok::<_, io::Error>));
+    l.handle().spawn(items.forward(tx).then(|res| {
+        assert!(res.is_ok());
+        Ok(())
+    }));
+    assert_eq!(t!(l.run(rx.collect())), (0..10).collect::<Vec<_>>());
+}
+
+#[test]
+fn close_drains_buffered() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let (tx, mut rx) = t!(channel::channel(&l.handle()));
+
+    // Use the inherent `send` rather than `Sink::send`.
+    let tx = &tx;
+    t!(tx.send(1));
+    t!(tx.send(2));
+    assert!(!tx.is_closed());
+    rx.close();
+    assert!(tx.is_closed());
+    match tx.send(3) {
+        Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => {}
+        other => panic!("unexpected: {:?}", other),
+    }
+    assert_eq!(t!(l.run(rx.collect())), vec![1, 2]);
+}
+
+#[test]
+fn close_while_sending() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let (tx, mut rx) = t!(channel::channel(&l.handle()));
+
+    // Every message the sender was told it sent must be received, however
+    // the close races with it.
+    let t = thread::spawn(move || {
+        let mut sent = 0;
+        while (&tx).send(sent).is_ok() {
+            sent += 1;
+        }
+        sent
+    });
+    thread::sleep(Duration::from_millis(10));
+    rx.close();
+    let received = t!(l.run(rx.collect()));
+    let sent = t.join().unwrap();
+    assert_eq!(received, (0..sent).collect::<Vec<_>>());
+}
+
+#[test]
+fn closed_resolves() {
+    drop(env_logger::init());
+    let l = t!(Core::new());
+    let (tx, rx) = t!(channel::bounded::<i32>(1, &l.handle()));
+
+    let t = thread::spawn(move || {
+        t!(tx.closed().wait());
+        tx.is_closed()
+    });
+    thread::sleep(Duration::from_millis(20));
+    drop(rx);
+    assert!(t.join().unwrap());
+}
+
+#[test]
+fn broadcast_to_every_receiver() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let handle = l.handle();
+    let (tx, rx1) = t!(channel::broadcast(16, &handle));
+    let rx2 = t!(tx.subscribe(&handle));
+    assert_eq!(tx.receiver_count(), 2);
+
+    let t = thread::spawn(move || {
+        for i in 0..10 {
+            t!(tx.send(i));
+        }
+    });
+    let both = rx1.collect().join(rx2.collect());
+    let (a, b) = match l.run(both) {
+        Ok(pair) => pair,
+        Err(e) => panic!("receiver failed: {}", e),
+    };
+    t.join().unwrap();
+    assert_eq!(a, (0..10).collect::<Vec<_>>());
+    assert_eq!(a, b);
+}
+
+#[test]
+fn broadcast_lagged() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let (tx, rx) = t!(channel::broadcast(2, &l.handle()));
+
+    for i in 0..5 {
+        t!(tx.send(i));
+    }
+    drop(tx);
+    let (first, rx) = match l.run(rx.into_future()) {
+        Ok(_) => panic!("receiver should have lagged"),
+        Err((e, rx)) => (e, rx),
+    };
+    assert_eq!(first.skipped(), 3);
+    match l.run(rx.collect()) {
+        Ok(rest) => assert_eq!(rest, vec![3, 4]),
+        Err(e) => panic!("receiver failed: {}", e),
+    }
+}
+
+#[test]
+fn broadcast_without_receivers() {
+    drop(env_logger::init());
+    let l = t!(Core::new());
+    let (tx, rx) = t!(channel::broadcast(2, &l.handle()));
+    drop(rx);
+    assert_eq!(tx.receiver_count(), 0);
+    assert!(tx.send(1).is_err());
+}
+
+#[test]
+fn watch_sees_latest() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let (tx, rx) = t!(channel::watch("a", &l.handle()));
+
+    let (first, rx) = match l.run(rx.into_future()) {
+        Ok(pair) => pair,
+        Err(_) => panic!("receiver failed"),
+    };
+    assert_eq!(first, Some("a"));
+
+    t!(tx.send("b"));
+    t!(tx.send("c"));
+    assert_eq!(rx.latest(), "c");
+    drop(tx);
+    assert_eq!(t!(l.run(rx.collect())), vec!["c"]);
+}
+
+#[test]
+fn watch_from_other_thread() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let (tx, rx) = t!(channel::watch(0, &l.handle()));
+
+    let t = thread::spawn(move || {
+        for i in 1..100 {
+            t!(tx.send(i));
+        }
+    });
+    let seen = t!(l.run(rx.collect()));
+    t.join().unwrap();
+    assert_eq!(seen.last(), Some(&99));
+    assert!(seen.windows(2).all(|w| w[0] < w[1]));
+}
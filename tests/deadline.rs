@@ -0,0 +1,61 @@
+extern crate env_logger;
+extern crate futures;
+extern crate tokio_core;
+
+use std::io;
+use std::time::{Instant, Duration};
+
+use futures::Future;
+use futures::stream::Stream;
+use tokio_core::reactor::{Core, Interval};
+use tokio_core::reactor::{FutureTimeoutExt, StreamTimeoutExt};
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+#[test]
+fn future_times_out() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let dur = Duration::from_millis(10);
+    let start = Instant::now();
+    let never = futures::empty::<(), io::Error>();
+    let err = l.run(never.timeout(dur, &l.handle())).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    assert!(start.elapsed() >= dur);
+}
+
+#[test]
+fn future_completes() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let handle = l.handle();
+    let done = futures::finished::<u32, io::Error>(3);
+    let done = done.timeout(Duration::from_secs(60), &handle);
+    assert_eq!(t!(l.run(done)), 3);
+
+    let boom = io::Error::new(io::ErrorKind::Other, "boom");
+    let err = futures::failed::<(), io::Error>(boom);
+    let err = l.run(err.timeout(Duration::from_secs(60), &handle)).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::Other);
+}
+
+#[test]
+fn stream_per_item() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let handle = l.handle();
+
+    let fast = t!(Interval::new(Duration::from_millis(10), &handle));
+    let fast = fast.timeout_per_item(Duration::from_secs(60), &handle);
+    assert_eq!(t!(l.run(fast.take(3).collect())).len(), 3);
+
+    let slow = t!(Interval::new(Duration::from_secs(60), &handle));
+    let slow = slow.timeout_per_item(Duration::from_millis(10), &handle);
+    let err = l.run(slow.into_future()).err().unwrap().0;
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+}
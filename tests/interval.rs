@@ -0,0 +1,86 @@
+extern crate env_logger;
+extern crate futures;
+extern crate tokio_core;
+
+use std::io;
+use std::time::{Instant, Duration};
+
+use futures::stream::Stream;
+use tokio_core::reactor::{Core, Interval};
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+#[test]
+fn single() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let dur = Duration::from_millis(10);
+    let interval = t!(Interval::new(dur, &l.handle()));
+    let start = Instant::now();
+    t!(l.run(interval.take(1).collect()));
+    assert!(start.elapsed() >= dur);
+}
+
+#[test]
+fn two_times() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let dur = Duration::from_millis(10);
+    let interval = t!(Interval::new(dur, &l.handle()));
+    let start = Instant::now();
+    let result = t!(l.run(interval.take(2).collect()));
+    assert!(start.elapsed() >= dur * 2);
+    assert_eq!(result, vec![(), ()]);
+}
+
+#[test]
+fn no_drift() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let dur = Duration::from_millis(10);
+    let start = Instant::now();
+    let interval = t!(Interval::new_at(start + dur, dur, &l.handle()));
+
+    // Block the loop past the next two ticks, which should then be delivered
+    // immediately rather than pushing the whole schedule back.
+    let mut first = true;
+    let ticks = interval.take(3).for_each(|()| {
+        if first {
+            std::thread::sleep(dur * 2 + dur / 2);
+            first = false;
+        }
+        Ok(())
+    });
+    t!(l.run(ticks));
+    let elapsed = start.elapsed();
+    assert!(elapsed >= dur * 3);
+    assert!(elapsed < dur * 5);
+}
+
+#[test]
+fn reset() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let mut interval = t!(Interval::new(Duration::from_secs(60), &l.handle()));
+    let start = Instant::now();
+    interval.reset(start + Duration::from_millis(10));
+    interval.set_interval(Duration::from_millis(20));
+    assert_eq!(interval.interval(), Duration::from_millis(20));
+    t!(l.run(interval.take(2).collect()));
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(40));
+    assert!(elapsed < Duration::from_secs(60));
+}
+
+#[test]
+fn zero_period() {
+    drop(env_logger::init());
+    let l = t!(Core::new());
+    let err = Interval::new(Duration::new(0, 0), &l.handle()).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
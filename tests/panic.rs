@@ -0,0 +1,85 @@
+extern crate env_logger;
+extern crate futures;
+extern crate tokio_core;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use tokio_core::reactor::{Core, JoinError};
+
+#[test]
+fn hook_catches_task_panic() {
+    drop(env_logger::init());
+    let mut lp = Core::new().unwrap();
+
+    let panics = Rc::new(RefCell::new(Vec::new()));
+    let panics2 = panics.clone();
+    lp.set_panic_hook(move |id, payload| {
+        let msg = *payload.downcast::<&'static str>().unwrap();
+        panics2.borrow_mut().push((id, msg));
+    });
+
+    let bad = lp.handle().spawn_join(futures::lazy(|| -> Result<(), ()> {
+        panic!("task failed")
+    }));
+    let (tx, rx) = futures::oneshot();
+    lp.handle().spawn(futures::lazy(|| {
+        tx.complete(1);
+        Ok(())
+    }));
+
+    assert_eq!(lp.run(rx).unwrap(), 1);
+    match lp.run(bad) {
+        Err(JoinError::Dropped) => {}
+        other => panic!("unexpected: {:?}", other),
+    }
+    let panics = panics.borrow();
+    assert_eq!(panics.len(), 1);
+    assert_eq!(panics[0].1, "task failed");
+}
+
+#[test]
+#[should_panic(expected = "task failed")]
+fn panics_propagate_by_default() {
+    drop(env_logger::init());
+    let mut lp = Core::new().unwrap();
+    lp.handle().spawn(futures::lazy(|| -> Result<(), ()> {
+        panic!("task failed")
+    }));
+    lp.run(futures::empty::<(), ()>()).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "task failed")]
+fn take_panic_hook() {
+    drop(env_logger::init());
+    let mut lp = Core::new().unwrap();
+    lp.set_panic_hook(|_, _| {});
+    assert!(lp.take_panic_hook().is_some());
+    lp.handle().spawn(futures::lazy(|| -> Result<(), ()> {
+        panic!("task failed")
+    }));
+    lp.run(futures::empty::<(), ()>()).unwrap();
+}
+
+#[test]
+fn abort_after_panic() {
+    drop(env_logger::init());
+    let mut lp = Core::new().unwrap();
+    lp.set_panic_hook(|_, _| {});
+
+    let bad = lp.handle().spawn_join(futures::lazy(|| -> Result<(), ()> {
+        panic!("task failed")
+    }));
+    lp.run_until_idle();
+
+    // The next task takes the slot the panicked task was removed from, and
+    // aborting the old handle mustn't touch it.
+    let (tx, rx) = futures::oneshot::<i32>();
+    let good = lp.handle().spawn_join(rx);
+    bad.abort();
+    lp.run_until_idle();
+
+    tx.complete(5);
+    assert_eq!(lp.run(good).unwrap(), 5);
+}
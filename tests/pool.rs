@@ -0,0 +1,137 @@
+extern crate env_logger;
+extern crate futures;
+extern crate tokio_core;
+
+use std::collections::HashSet;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use futures::Future;
+use tokio_core::reactor::{Balance, CorePool};
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+fn thread_name() -> String {
+    thread::current().name().unwrap().to_string()
+}
+
+#[test]
+fn round_robin() {
+    drop(env_logger::init());
+    let pool = t!(CorePool::new(3));
+    let handle = pool.handle();
+    assert_eq!(handle.len(), 3);
+
+    let (tx, rx) = mpsc::channel();
+    for _ in 0..6 {
+        let tx = tx.clone();
+        t!(handle.spawn(move |_| {
+            tx.send(thread_name()).unwrap();
+            Ok(())
+        }));
+    }
+    let names = (0..6).map(|_| rx.recv().unwrap()).collect::<HashSet<_>>();
+    assert_eq!(names.len(), 3);
+    assert!(names.contains("core-pool-0"));
+
+    let summaries = t!(pool.shutdown());
+    assert_eq!(summaries.len(), 3);
+    assert!(summaries.iter().all(|s| s.is_graceful()));
+}
+
+#[test]
+fn spawn_on() {
+    drop(env_logger::init());
+    let pool = t!(CorePool::new(2));
+    let handle = pool.handle();
+
+    for i in 0..2 {
+        let (tx, rx) = futures::oneshot();
+        t!(handle.spawn_on(i, move |_| {
+            tx.complete(thread_name());
+            Ok(())
+        }));
+        assert_eq!(rx.wait().unwrap(), format!("core-pool-{}", i));
+    }
+}
+
+#[test]
+fn least_loaded() {
+    drop(env_logger::init());
+    let pool = t!(CorePool::with_config(2, Balance::LeastLoaded, |_, b| {
+        b.max_tasks_per_turn(64);
+    }));
+    let handle = pool.handle();
+
+    // Keep the first event loop busy with tasks which never finish, so
+    // everything else ends up on the second one.
+    for _ in 0..3 {
+        t!(handle.spawn_on(0, |_| futures::empty()));
+    }
+    assert_eq!(handle.pool_task_count(0), 3);
+    for _ in 0..2 {
+        let (tx, rx) = futures::oneshot();
+        t!(handle.spawn(move |_| {
+            tx.complete(thread_name());
+            Ok(())
+        }));
+        assert_eq!(rx.wait().unwrap(), "core-pool-1");
+    }
+
+    // Completed tasks no longer count towards the load.
+    for _ in 0..100 {
+        if handle.pool_task_count(1) == 0 {
+            break
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(handle.pool_task_count(1), 0);
+}
+
+#[test]
+fn least_loaded_counts_pool_tasks() {
+    drop(env_logger::init());
+    let pool = t!(CorePool::with_config(2, Balance::LeastLoaded, |_, _| {}));
+    let handle = pool.handle();
+
+    // Tasks spawned directly on an event loop don't count towards its load,
+    // so the second event loop is still picked over the first.
+    for _ in 0..2 {
+        t!(handle.spawn_on(0, |_| futures::empty()));
+    }
+    for _ in 0..3 {
+        t!(handle.remote(1).spawn(|_| futures::empty()));
+    }
+    assert_eq!(handle.pool_task_count(1), 0);
+    for _ in 0..2 {
+        let (tx, rx) = futures::oneshot();
+        t!(handle.spawn(move |_| {
+            tx.complete(thread_name());
+            Ok(())
+        }));
+        assert_eq!(rx.wait().unwrap(), "core-pool-1");
+    }
+}
+
+#[test]
+fn shutdown_from_handle() {
+    drop(env_logger::init());
+    let pool = t!(CorePool::with_config(2, Balance::RoundRobin, |_, b| {
+        b.shutdown_grace_period(Duration::from_millis(10));
+    }));
+    let handle = pool.handle();
+    t!(handle.spawn_on(1, |_| futures::empty()));
+
+    handle.shutdown();
+    let summaries = t!(pool.join());
+    assert_eq!(summaries[0].cancelled(), 0);
+    assert_eq!(summaries[1].cancelled(), 1);
+    assert_eq!(handle.pool_task_count(1), 0);
+    assert!(handle.spawn(|_| Ok(())).is_err());
+}
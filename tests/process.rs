@@ -0,0 +1,142 @@
+#![cfg(unix)]
+
+extern crate env_logger;
+extern crate futures;
+extern crate tokio_core;
+
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use futures::{future, Future};
+use tokio_core::io::{copy, read_to_end, write_all, Io};
+use tokio_core::process::CommandExt;
+use tokio_core::reactor::Core;
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+#[test]
+fn exit_status() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let child = t!(Command::new("/bin/sh").arg("-c").arg("exit 3")
+                                          .spawn_async(&l.handle()));
+    let status = t!(l.run(child));
+    assert_eq!(status.code(), Some(3));
+}
+
+#[test]
+fn cat_round_trip() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let mut child = t!(Command::new("cat")
+                               .stdin(Stdio::piped())
+                               .stdout(Stdio::piped())
+                               .spawn_async(&l.handle()));
+    let stdin = child.stdin().unwrap();
+    let stdout = child.stdout().unwrap();
+
+    let msg = vec![b'a'; 1024 * 1024];
+    let write = write_all(stdin, msg.clone()).map(|(stdin, _)| drop(stdin));
+    let read = read_to_end(stdout, Vec::new()).map(|(_, buf)| buf);
+    let ((), buf, status) = t!(l.run(write.join3(read, child)));
+    assert!(status.success());
+    assert!(buf == msg);
+}
+
+#[test]
+fn pipeline() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let handle = l.handle();
+    let spawn_cat = || {
+        Command::new("cat").stdin(Stdio::piped())
+                           .stdout(Stdio::piped())
+                           .spawn_async(&handle)
+    };
+    let mut first = t!(spawn_cat());
+    let mut second = t!(spawn_cat());
+
+    let first_in = first.stdin().unwrap();
+    let first_out = first.stdout().unwrap();
+    let second_in = second.stdin().unwrap();
+    let second_out = second.stdout().unwrap();
+    let msg = vec![b'b'; 64 * 1024];
+    let expected = msg.clone();
+
+    // The child's stdio are ordinary `Io` objects, so they can be split and
+    // fed through the generic combinators.
+    let done = future::lazy(move || {
+        let (first_out, _) = first_out.split();
+        let (_, second_in) = second_in.split();
+        let pipe = copy(first_out, second_in);
+        let write = write_all(first_in, msg).map(|(stdin, _)| drop(stdin));
+        let read = read_to_end(second_out, Vec::new()).map(|(_, buf)| buf);
+        write.join3(pipe, read).and_then(|((), n, buf)| {
+            first.join(second).map(move |statuses| (n, statuses, buf))
+        })
+    });
+    let (n, (a, b), buf) = t!(l.run(done));
+    assert_eq!(n, expected.len() as u64);
+    assert!(a.success() && b.success());
+    assert!(buf == expected);
+}
+
+#[test]
+fn kill() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let mut child = t!(Command::new("/bin/sh").arg("-c").arg("sleep 1000")
+                                              .spawn_async(&l.handle()));
+    t!(child.kill());
+    let status = t!(l.run(child));
+    assert!(!status.success());
+}
+
+#[test]
+fn many_children() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let children = (0..10).map(|i| {
+        t!(Command::new("/bin/sh").arg("-c").arg(format!("exit {}", i))
+                                  .spawn_async(&l.handle()))
+    }).collect::<Vec<_>>();
+    let statuses = t!(l.run(futures::collect(children)));
+    for (i, status) in statuses.iter().enumerate() {
+        assert_eq!(status.code(), Some(i as i32));
+    }
+}
+
+#[test]
+fn drop_sibling() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let handle = l.handle();
+    let child = t!(Command::new("/bin/sh").arg("-c").arg("sleep 0.1; exit 4")
+                                          .spawn_async(&handle));
+    let mut sleeper = t!(Command::new("sleep").arg("1000")
+                                              .spawn_async(&handle));
+
+    // Wait for the first child in its own task...
+    let (tx, rx) = futures::oneshot();
+    handle.spawn(child.then(|status| {
+        tx.complete(status.map(|s| s.code()));
+        Ok(())
+    }));
+    l.turn(Some(Duration::new(0, 0)));
+
+    // ... then make another task the last to have polled the shared SIGCHLD
+    // listener, and drop it while the first child is still running.
+    t!(l.run(futures::lazy(move || {
+        assert!(t!(sleeper.poll()).is_not_ready());
+        t!(sleeper.kill());
+        Ok::<_, ()>(())
+    })));
+
+    let code = t!(t!(l.run(rx)));
+    assert_eq!(code, Some(4));
+}
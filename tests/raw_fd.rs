@@ -0,0 +1,78 @@
+#![cfg(unix)]
+
+extern crate env_logger;
+extern crate futures;
+extern crate libc;
+extern crate tokio_core;
+
+use std::io;
+use std::os::unix::prelude::*;
+
+use futures::Future;
+use tokio_core::io::{read_exact, write_all};
+use tokio_core::reactor::{Core, PollEvented, RawFdSource};
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+fn pipe() -> io::Result<(RawFd, RawFd)> {
+    let mut fds = [0; 2];
+    unsafe {
+        if libc::pipe(fds.as_mut_ptr()) < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        for &fd in fds.iter() {
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+            libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK);
+        }
+    }
+    Ok((fds[0], fds[1]))
+}
+
+fn is_open(fd: RawFd) -> bool {
+    unsafe { libc::fcntl(fd, libc::F_GETFD) >= 0 }
+}
+
+#[test]
+fn pipe_round_trip() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let handle = l.handle();
+    let (rd, wr) = t!(pipe());
+
+    let reader = t!(PollEvented::from_raw_fd(rd, &handle));
+    let writer = unsafe { RawFdSource::from_raw_fd(wr) };
+    let writer = t!(PollEvented::new(writer, &handle));
+
+    // More than fits in a pipe buffer, so both sides have to block.
+    let data = vec![7u8; 256 * 1024];
+    let write = write_all(writer, data.clone());
+    let read = read_exact(reader, vec![0; data.len()]);
+    let ((writer, _), (reader, buf)) = t!(l.run(write.join(read)));
+    assert!(buf == data);
+
+    drop(writer);
+    assert!(!is_open(wr));
+    drop(reader);
+    assert!(is_open(rd));
+    unsafe {
+        libc::close(rd);
+    }
+}
+
+#[test]
+fn into_raw_fd_releases_ownership() {
+    let (rd, wr) = t!(pipe());
+    let source = unsafe { RawFdSource::from_raw_fd(rd) };
+    assert!(source.is_owned());
+    assert_eq!(source.into_raw_fd(), rd);
+    assert!(is_open(rd));
+    unsafe {
+        libc::close(rd);
+        libc::close(wr);
+    }
+}
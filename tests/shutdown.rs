@@ -0,0 +1,93 @@
+extern crate env_logger;
+extern crate futures;
+extern crate tokio_core;
+
+use std::io;
+use std::net;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures::Future;
+use futures::stream::Stream;
+use tokio_core::net::TcpListener;
+use tokio_core::reactor::{Core, JoinError, Timeout};
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+#[test]
+fn signal_resolves() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let handle = l.handle();
+
+    let done = Arc::new(AtomicBool::new(false));
+    let done2 = done.clone();
+    handle.spawn(handle.shutdown_signal().map(move |()| {
+        done2.store(true, Ordering::SeqCst);
+    }));
+
+    let remote = l.remote();
+    let t = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(10));
+        remote.shutdown();
+    });
+    let summary = l.run_until_shutdown();
+    t.join().unwrap();
+
+    assert!(done.load(Ordering::SeqCst));
+    assert!(summary.is_graceful());
+    assert_eq!(summary.cancelled(), 0);
+    assert!(handle.shutdown_signal().wait().is_ok());
+}
+
+#[test]
+fn grace_period_cancels_tasks() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    l.set_shutdown_grace_period(Duration::from_millis(50));
+    let handle = l.handle();
+
+    let stuck = handle.spawn_join(futures::empty::<(), ()>());
+    let timeout = t!(Timeout::new(Duration::from_millis(10), &handle));
+    let quick = handle.spawn_join(timeout);
+
+    l.remote().shutdown();
+    let start = Instant::now();
+    let summary = l.run_until_shutdown();
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    assert_eq!(summary.cancelled(), 1);
+    assert_eq!(l.task_count(), 0);
+
+    assert!(quick.wait().is_ok());
+    match stuck.wait() {
+        Err(JoinError::Dropped) => {}
+        other => panic!("unexpected: {:?}", other),
+    }
+}
+
+#[test]
+fn listener_stops_accepting() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let handle = l.handle();
+
+    let srv = t!(TcpListener::bind(&t!("127.0.0.1:0".parse()), &handle));
+    let addr = t!(srv.local_addr());
+    let remote = l.remote();
+    let t = thread::spawn(move || {
+        t!(net::TcpStream::connect(&addr));
+        remote.shutdown();
+    });
+
+    let incoming = srv.incoming().fold(0, |n, _| Ok::<_, io::Error>(n + 1));
+    let accepted = t!(l.run(incoming));
+    t.join().unwrap();
+    assert!(accepted <= 1);
+}
@@ -0,0 +1,100 @@
+#![cfg(unix)]
+
+extern crate env_logger;
+extern crate futures;
+extern crate libc;
+extern crate tokio_core;
+
+use std::sync::mpsc;
+use std::thread;
+
+use futures::Future;
+use futures::stream::Stream;
+use tokio_core::reactor::Core;
+use tokio_core::reactor::signal::{self, Signal};
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+fn send_signal(signum: i32) {
+    unsafe {
+        assert_eq!(libc::kill(libc::getpid(), signum), 0);
+    }
+}
+
+#[test]
+fn simple() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let signal = t!(Signal::new(signal::SIGUSR1, &l.handle()));
+    send_signal(signal::SIGUSR1);
+    let (item, _) = t!(l.run(signal.into_future()).map_err(|e| e.0));
+    assert_eq!(item, Some(signal::SIGUSR1));
+}
+
+#[test]
+fn multiple_listeners_and_loops() {
+    drop(env_logger::init());
+
+    let (tx, rx) = mpsc::channel();
+    let t = thread::spawn(move || {
+        let mut l = t!(Core::new());
+        let signal = t!(Signal::new(signal::SIGUSR2, &l.handle()));
+        tx.send(()).unwrap();
+        let (item, _) = t!(l.run(signal.into_future()).map_err(|e| e.0));
+        assert_eq!(item, Some(signal::SIGUSR2));
+    });
+    rx.recv().unwrap();
+
+    let mut l = t!(Core::new());
+    let a = t!(Signal::new(signal::SIGUSR2, &l.handle()));
+    let b = t!(Signal::new(signal::SIGUSR2, &l.handle()));
+    send_signal(signal::SIGUSR2);
+    let both = a.into_future().join(b.into_future()).map_err(|e| e.0);
+    let ((a, _), (b, _)) = t!(l.run(both));
+    assert_eq!(a, Some(signal::SIGUSR2));
+    assert_eq!(b, Some(signal::SIGUSR2));
+    t.join().unwrap();
+}
+
+#[test]
+fn drop_then_recreate() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    for _ in 0..3 {
+        let signal = t!(Signal::new(signal::SIGHUP, &l.handle()));
+        send_signal(signal::SIGHUP);
+        let (item, _) = t!(l.run(signal.into_future()).map_err(|e| e.0));
+        assert_eq!(item, Some(signal::SIGHUP));
+    }
+}
+
+#[test]
+fn forbidden() {
+    let l = t!(Core::new());
+    assert!(Signal::new(libc::SIGKILL, &l.handle()).is_err());
+    assert!(Signal::new(0, &l.handle()).is_err());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn handler_preserves_errno() {
+    drop(env_logger::init());
+    let l = t!(Core::new());
+    let _signal = t!(Signal::new(signal::SIGWINCH, &l.handle()));
+
+    // Deliver enough signals without polling to fill up the self-pipe, so
+    // the handler's writes start failing, and make sure none of that leaks
+    // out into `errno`.
+    for _ in 0..100_000 {
+        unsafe {
+            *libc::__errno_location() = libc::EINTR;
+            assert_eq!(libc::raise(signal::SIGWINCH), 0);
+            assert_eq!(*libc::__errno_location(), libc::EINTR);
+        }
+    }
+}
@@ -2,8 +2,14 @@ extern crate tokio_core;
 extern crate env_logger;
 extern crate futures;
 
+use std::rc::Rc;
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
 use futures::Future;
-use tokio_core::reactor::Core;
+use tokio_core::reactor::{Core, JoinError, JoinHandle};
 
 #[test]
 fn simple() {
@@ -21,7 +27,7 @@ fn simple() {
             tx2.complete(2);
             Ok(())
         })
-    });
+    }).unwrap();
 
     assert_eq!(lp.run(rx1.join(rx2)).unwrap(), (1, 2));
 }
@@ -41,9 +47,183 @@ fn spawn_in_poll() {
                 tx2.complete(2);
                 Ok(())
             })
-        });
+        }).unwrap();
         Ok(())
     }));
 
     assert_eq!(lp.run(rx1.join(rx2)).unwrap(), (1, 2));
 }
+
+#[test]
+fn join_result() {
+    drop(env_logger::init());
+    let mut lp = Core::new().unwrap();
+
+    let a = lp.handle().spawn_join(futures::lazy(|| Ok::<_, ()>(1)));
+    let b = lp.remote().spawn_join(|_| Err::<(), _>("boom"));
+    assert_eq!(lp.run(a).unwrap(), 1);
+    match lp.run(b) {
+        Err(JoinError::Failed("boom")) => {}
+        other => panic!("unexpected: {:?}", other),
+    }
+}
+
+#[test]
+fn join_from_other_thread() {
+    drop(env_logger::init());
+    let mut lp = Core::new().unwrap();
+    let remote = lp.remote();
+
+    let (tx, rx) = futures::oneshot();
+    let t = thread::spawn(move || {
+        let res = remote.spawn_join(|_| futures::lazy(|| Ok::<_, ()>(3))).wait();
+        tx.complete(res.unwrap());
+    });
+    assert_eq!(lp.run(rx).unwrap(), 3);
+    t.join().unwrap();
+}
+
+#[test]
+fn join_core_dropped() {
+    drop(env_logger::init());
+    let lp = Core::new().unwrap();
+    let remote = lp.remote();
+    let a = lp.handle().spawn_join(futures::empty::<(), ()>());
+    drop(lp);
+    match a.wait() {
+        Err(JoinError::Dropped) => {}
+        other => panic!("unexpected: {:?}", other),
+    }
+
+    let b = remote.spawn_join(|_| Ok::<(), ()>(()));
+    match b.wait() {
+        Err(JoinError::Dropped) => {}
+        other => panic!("unexpected: {:?}", other),
+    }
+}
+
+#[test]
+fn join_abort() {
+    drop(env_logger::init());
+    let mut lp = Core::new().unwrap();
+
+    let dropped = Rc::new(());
+    let flag = dropped.clone();
+    let a = lp.handle().spawn_join(futures::empty::<(), ()>().then(move |r| {
+        drop(flag);
+        r
+    }));
+    lp.turn(Some(Duration::from_millis(0)));
+    assert_eq!(Rc::strong_count(&dropped), 2);
+    a.abort();
+    match lp.run(a) {
+        Err(JoinError::Aborted) => {}
+        other => panic!("unexpected: {:?}", other),
+    }
+    assert_eq!(Rc::strong_count(&dropped), 1);
+
+    // Aborting before the task is spawned means it never runs.
+    let ran = Arc::new(AtomicBool::new(false));
+    let ran2 = ran.clone();
+    let b = lp.remote().spawn_join(move |_| {
+        ran2.store(true, Ordering::SeqCst);
+        Ok::<(), ()>(())
+    });
+    b.abort();
+    match lp.run(b) {
+        Err(JoinError::Aborted) => {}
+        other => panic!("unexpected: {:?}", other),
+    }
+    assert!(!ran.load(Ordering::SeqCst));
+}
+
+#[test]
+fn join_abort_self() {
+    drop(env_logger::init());
+    let mut lp = Core::new().unwrap();
+
+    let guard = Rc::new(());
+    let guard2 = guard.clone();
+    let (tx, rx) = futures::oneshot();
+    let a = lp.handle().spawn_join(rx.map_err(|_| ()).and_then(move |h: JoinHandle<(), ()>| {
+        h.abort();
+        futures::empty::<(), ()>().map(move |()| drop(guard2))
+    }));
+    tx.complete(a);
+    for _ in 0..10 {
+        if Rc::strong_count(&guard) == 1 {
+            break
+        }
+        lp.turn(Some(Duration::from_millis(10)));
+    }
+    assert_eq!(Rc::strong_count(&guard), 1);
+}
+
+#[test]
+fn spawn_after_core_dropped() {
+    drop(env_logger::init());
+    let lp = Core::new().unwrap();
+    let remote = lp.remote();
+    drop(lp);
+
+    let ran = Arc::new(AtomicBool::new(false));
+    let ran2 = ran.clone();
+    let res = remote.spawn(move |_| {
+        ran2.store(true, Ordering::SeqCst);
+        Ok(())
+    });
+    assert!(res.is_err());
+    assert!(!ran.load(Ordering::SeqCst));
+}
+
+#[test]
+fn try_run_and_try_turn() {
+    drop(env_logger::init());
+    let mut lp = Core::new().unwrap();
+
+    let turn = lp.try_turn(Some(Duration::new(0, 0))).unwrap();
+    assert_eq!(turn.tasks(), 0);
+    assert_eq!(lp.try_run(futures::finished::<_, ()>(1)).unwrap(), Ok(1));
+    assert_eq!(lp.try_run(futures::failed::<(), _>(2)).unwrap(), Err(2));
+}
+
+#[test]
+fn remote_spawns_stay_in_order() {
+    drop(env_logger::init());
+    let mut lp = Core::new().unwrap();
+    let remote = lp.remote();
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let (done_tx, done_rx) = futures::oneshot();
+    let (started_tx, started_rx) = mpsc::channel();
+
+    // Send far more messages than the event loop's queue holds before they
+    // spill over while the event loop isn't running to receive them, and then
+    // some more once it's catching up on those.
+    let order2 = order.clone();
+    let t = thread::spawn(move || {
+        let mut done_tx = Some(done_tx);
+        for i in 0..10_000 {
+            let order = order2.clone();
+            let done_tx = if i == 9_999 { done_tx.take() } else { None };
+            remote.spawn(move |_| {
+                order.lock().unwrap().push(i);
+                if let Some(tx) = done_tx {
+                    tx.complete(());
+                }
+                Ok(())
+            }).unwrap();
+            if i == 5_000 {
+                started_tx.send(()).unwrap();
+                while order2.lock().unwrap().is_empty() {
+                    thread::yield_now();
+                }
+            }
+        }
+    });
+    started_rx.recv().unwrap();
+    lp.run(done_rx).unwrap();
+    t.join().unwrap();
+
+    let order = order.lock().unwrap();
+    assert_eq!(*order, (0..10_000).collect::<Vec<_>>());
+}
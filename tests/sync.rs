@@ -0,0 +1,158 @@
+extern crate env_logger;
+extern crate futures;
+extern crate tokio_core;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::thread;
+
+use futures::Future;
+use futures::future;
+use tokio_core::reactor::Core;
+use tokio_core::sync::{self, local};
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+#[test]
+fn local_mutex_is_fifo() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let handle = l.handle();
+    let mutex = local::Mutex::new(Vec::new());
+
+    let guard = mutex.try_lock().unwrap();
+    assert!(mutex.try_lock().is_none());
+    for i in 0..3 {
+        handle.spawn(mutex.lock().map(move |mut v| v.push(i)));
+    }
+    l.run_until_idle();
+    assert!(guard.is_empty());
+    drop(guard);
+
+    l.run_until_idle();
+    assert_eq!(*mutex.try_lock().unwrap(), vec![0, 1, 2]);
+}
+
+#[test]
+fn mutex_across_threads() {
+    drop(env_logger::init());
+    let mutex = sync::Mutex::new(0);
+    let threads = (0..4).map(|_| {
+        let mutex = mutex.clone();
+        thread::spawn(move || {
+            for _ in 0..1000 {
+                let mut n = mutex.lock().wait().unwrap();
+                *n += 1;
+            }
+        })
+    }).collect::<Vec<_>>();
+    for t in threads {
+        t.join().unwrap();
+    }
+    assert_eq!(*mutex.lock().wait().unwrap(), 4000);
+}
+
+#[test]
+fn rwlock_writer_holds_up_readers() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let handle = l.handle();
+    let lock = local::RwLock::new(0);
+    let log = Rc::new(RefCell::new(Vec::new()));
+
+    let r1 = lock.try_read().unwrap();
+    let r2 = lock.try_read().unwrap();
+    let log2 = log.clone();
+    handle.spawn(lock.write().map(move |mut n| {
+        *n += 1;
+        log2.borrow_mut().push("write");
+    }));
+    let log2 = log.clone();
+    handle.spawn(lock.read().map(move |n| {
+        assert_eq!(*n, 1);
+        log2.borrow_mut().push("read");
+    }));
+    l.run_until_idle();
+    assert!(log.borrow().is_empty());
+    assert!(lock.try_read().is_none());
+
+    drop(r1);
+    l.run_until_idle();
+    assert!(log.borrow().is_empty());
+    drop(r2);
+    l.run_until_idle();
+    assert_eq!(*log.borrow(), vec!["write", "read"]);
+}
+
+#[test]
+fn semaphore_permits() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let sem = local::Semaphore::new(3);
+
+    let one = sem.try_acquire(1).unwrap();
+    assert_eq!(sem.available_permits(), 2);
+
+    // Waiting for three permits holds up smaller requests behind it.
+    let mut many = sem.acquire_many(3);
+    l.run(future::lazy(|| {
+        assert!(many.poll().unwrap().is_not_ready());
+        Ok::<_, ()>(())
+    })).unwrap();
+    assert!(sem.try_acquire(1).is_none());
+
+    // Giving up our place lets them through again.
+    drop(many);
+    let two = sem.try_acquire(2).unwrap();
+    assert_eq!(two.count(), 2);
+    drop(one);
+    drop(two);
+    assert_eq!(sem.available_permits(), 3);
+
+    let permit = t!(l.run(sem.acquire_many(3)));
+    assert_eq!(sem.available_permits(), 0);
+    drop(permit);
+    assert_eq!(sem.available_permits(), 3);
+}
+
+#[test]
+fn notify() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let handle = l.handle();
+    let notify = local::Notify::new();
+
+    // A notification with nobody waiting is kept for the next waiter.
+    notify.notify_one();
+    t!(l.run(notify.notified()));
+
+    let woken = Rc::new(RefCell::new(0));
+    for _ in 0..3 {
+        let woken = woken.clone();
+        handle.spawn(notify.notified().map(move |()| *woken.borrow_mut() += 1));
+    }
+    l.run_until_idle();
+    notify.notify_one();
+    l.run_until_idle();
+    assert_eq!(*woken.borrow(), 1);
+    notify.notify_all();
+    l.run_until_idle();
+    assert_eq!(*woken.borrow(), 3);
+}
+
+#[test]
+fn notify_across_threads() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let notify = sync::Notify::new();
+
+    let notify2 = notify.clone();
+    let t = thread::spawn(move || notify2.notify_one());
+    t!(l.run(notify.notified()));
+    t.join().unwrap();
+}
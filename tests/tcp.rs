@@ -1,5 +1,7 @@
 extern crate env_logger;
 extern crate futures;
+#[cfg(unix)]
+extern crate libc;
 extern crate tokio_core;
 
 use std::net;
@@ -83,3 +85,66 @@ fn accept2() {
     mine.unwrap();
     t.join().unwrap();
 }
+
+#[cfg(target_os = "linux")]
+#[test]
+fn bind_reuseport_distributes() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tokio_core::reactor::CorePool;
+
+    drop(env_logger::init());
+    let pool = t!(CorePool::new(2));
+    let handle = pool.handle();
+    let counts = Arc::new([AtomicUsize::new(0), AtomicUsize::new(0)]);
+
+    // Bind the first listener to a fresh port and the second one to the same
+    // address, each on its own event loop.
+    let (tx, rx) = channel();
+    let mut addr = t!("127.0.0.1:0".parse());
+    for i in 0..2 {
+        let tx = tx.clone();
+        let counts = counts.clone();
+        t!(handle.spawn_on(i, move |handle| {
+            let srv = t!(TcpListener::bind_reuseport(&addr, handle));
+            tx.send(t!(srv.local_addr())).unwrap();
+            srv.incoming().for_each(move |_| {
+                counts[i].fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }).map_err(|e| panic!("accept failed: {}", e))
+        }));
+        addr = rx.recv().unwrap();
+    }
+
+    let clients = (0..64).map(|_| t!(net::TcpStream::connect(&addr)))
+                         .collect::<Vec<_>>();
+    for _ in 0..500 {
+        let total = counts[0].load(Ordering::SeqCst) +
+                    counts[1].load(Ordering::SeqCst);
+        if total == clients.len() {
+            break
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(counts[0].load(Ordering::SeqCst) > 0);
+    assert!(counts[1].load(Ordering::SeqCst) > 0);
+    assert_eq!(counts[0].load(Ordering::SeqCst) +
+               counts[1].load(Ordering::SeqCst), 64);
+
+    let summaries = t!(pool.shutdown());
+    assert!(summaries.iter().all(|s| s.is_graceful()));
+}
+
+#[cfg(unix)]
+#[test]
+fn bind_reuseport_cloexec() {
+    use std::os::unix::prelude::*;
+
+    drop(env_logger::init());
+    let l = t!(Core::new());
+    let addr = t!("127.0.0.1:0".parse());
+    let srv = t!(TcpListener::bind_reuseport(&addr, &l.handle()));
+    let flags = unsafe { libc::fcntl(srv.as_raw_fd(), libc::F_GETFD) };
+    assert!(flags & libc::FD_CLOEXEC != 0);
+}
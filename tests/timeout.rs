@@ -4,6 +4,7 @@ extern crate tokio_core;
 
 use std::time::{Instant, Duration};
 
+use futures::Future;
 use tokio_core::reactor::{Core, Timeout};
 
 macro_rules! t {
@@ -35,3 +36,68 @@ fn two() {
     let timeout = t!(Timeout::new(dur, &l.handle()));
     t!(l.run(timeout));
 }
+
+#[test]
+fn many() {
+    drop(env_logger::init());
+
+    let mut l = t!(Core::new());
+    let handle = l.handle();
+    let start = Instant::now();
+    let mut timeouts = Vec::new();
+    for i in 0..1000 {
+        let dur = Duration::from_millis(i % 50);
+        let timeout = t!(Timeout::new(dur, &handle));
+        // Cancel every other timeout by dropping it immediately
+        if i % 2 == 0 {
+            timeouts.push(timeout.map(move |()| {
+                assert!(start.elapsed() >= dur);
+            }));
+        }
+    }
+    t!(l.run(futures::collect(timeouts)));
+}
+
+#[test]
+fn coarse_resolution() {
+    drop(env_logger::init());
+
+    let mut l = t!(Core::with_timer_resolution(Duration::from_millis(50)));
+    let dur = Duration::from_millis(10);
+    let start = Instant::now();
+    let timeout = t!(Timeout::new(dur, &l.handle()));
+    t!(l.run(timeout));
+    // The timeout is rounded up to the first 50ms tick of the loop
+    assert!(start.elapsed() >= Duration::from_millis(40));
+}
+
+#[test]
+#[should_panic]
+fn zero_resolution() {
+    drop(Core::with_timer_resolution(Duration::new(0, 0)));
+}
+
+#[test]
+fn reset() {
+    drop(env_logger::init());
+
+    let mut l = t!(Core::new());
+    let dur = Duration::from_millis(10);
+    let mut timeout = t!(Timeout::new(Duration::from_secs(60), &l.handle()));
+
+    // Reset from outside the event loop, going through the message queue
+    let start = Instant::now();
+    timeout.reset(start + dur);
+    t!(l.run(&mut timeout));
+    assert!(start.elapsed() >= dur);
+    assert!(start.elapsed() < Duration::from_secs(60));
+
+    // Re-arm a timeout which has already fired, this time on the event loop
+    let start = Instant::now();
+    let timeout = t!(l.run(futures::lazy(move || {
+        timeout.reset(start + dur);
+        Ok::<_, ()>(timeout)
+    })));
+    t!(l.run(timeout));
+    assert!(start.elapsed() >= dur);
+}
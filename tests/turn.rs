@@ -0,0 +1,231 @@
+extern crate env_logger;
+extern crate futures;
+extern crate tokio_core;
+
+use std::cell::Cell;
+use std::io;
+use std::net;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use futures::{Future, Async};
+use tokio_core::net::UdpSocket;
+use tokio_core::reactor::{Core, Timeout};
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+#[test]
+fn turn_runs_spawned_task() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+
+    let hit = Rc::new(Cell::new(false));
+    let hit2 = hit.clone();
+    l.handle().spawn(futures::lazy(move || {
+        hit2.set(true);
+        Ok(())
+    }));
+
+    let turn = l.turn(Some(Duration::new(0, 0)));
+    assert!(hit.get());
+    assert_eq!(turn.tasks(), 1);
+    assert!(turn.events() >= 1);
+}
+
+#[test]
+fn turn_respects_max_wait() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+
+    let dur = Duration::from_millis(10);
+    let start = Instant::now();
+    let turn = l.turn(Some(dur));
+    assert!(start.elapsed() >= dur);
+    assert_eq!(turn.events(), 0);
+    assert_eq!(turn.tasks(), 0);
+}
+
+#[test]
+fn turn_fires_timeouts() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let handle = l.handle();
+
+    let fired = Rc::new(Cell::new(false));
+    let fired2 = fired.clone();
+    let timeout = t!(Timeout::new(Duration::from_millis(10), &handle));
+    handle.spawn(timeout.then(move |_| {
+        fired2.set(true);
+        Ok(())
+    }));
+
+    let mut timeouts = 0;
+    while !fired.get() {
+        timeouts += l.turn(None).timeouts();
+    }
+    assert_eq!(timeouts, 1);
+}
+
+#[test]
+fn task_budget() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    l.set_max_tasks_per_turn(2);
+
+    let hits = Rc::new(Cell::new(0));
+    for _ in 0..5 {
+        let hits = hits.clone();
+        l.handle().spawn(futures::lazy(move || {
+            hits.set(hits.get() + 1);
+            Ok(())
+        }));
+    }
+
+    // Leftover tasks mean the loop doesn't block even with no timeout.
+    assert_eq!(l.turn(None).tasks(), 2);
+    assert_eq!(hits.get(), 2);
+    assert_eq!(l.turn(None).tasks(), 2);
+    assert_eq!(hits.get(), 4);
+    assert_eq!(l.turn(None).tasks(), 1);
+    assert_eq!(hits.get(), 5);
+}
+
+#[test]
+fn message_budget() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    l.set_max_messages_per_turn(1);
+
+    // We're not running on the event loop here, so each of these is sent as
+    // a message.
+    let hits = Arc::new(AtomicUsize::new(0));
+    for _ in 0..3 {
+        let hits = hits.clone();
+        t!(l.remote().spawn(move |_| {
+            hits.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }));
+    }
+
+    for i in 1..4 {
+        l.turn(None);
+        assert_eq!(hits.load(Ordering::SeqCst), i);
+    }
+}
+
+#[test]
+fn io_budget() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    l.set_max_io_per_turn(1);
+    let handle = l.handle();
+
+    let hits = Rc::new(Cell::new(0));
+    let mut addrs = Vec::new();
+    for _ in 0..3 {
+        let socket = t!(UdpSocket::bind(&t!("127.0.0.1:0".parse()), &handle));
+        addrs.push(t!(socket.local_addr()));
+        let hits = hits.clone();
+        handle.spawn(futures::future::poll_fn(move || {
+            let mut buf = [0; 32];
+            match socket.recv_from(&mut buf) {
+                Ok((n, _)) => {
+                    assert_eq!(&buf[..n], b"hi");
+                    hits.set(hits.get() + 1);
+                    Ok(Async::Ready(()))
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    Ok(Async::NotReady)
+                }
+                Err(e) => panic!("recv failed: {}", e),
+            }
+        }));
+    }
+    while l.turn(Some(Duration::new(0, 0))).events() > 0 {}
+    assert_eq!(hits.get(), 0);
+
+    // None of the readiness events may be lost even though only one of them
+    // is dispatched per turn.
+    let sender = t!(net::UdpSocket::bind("127.0.0.1:0"));
+    for addr in addrs.iter() {
+        t!(sender.send_to(b"hi", addr));
+    }
+    for _ in 0..100 {
+        if hits.get() == 3 {
+            break
+        }
+        l.turn(Some(Duration::from_millis(10)));
+    }
+    assert_eq!(hits.get(), 3);
+}
+
+#[test]
+fn run_until_idle() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let handle = l.handle();
+
+    // A chain of tasks spawning each other all runs, but a task waiting on a
+    // timeout which hasn't expired doesn't keep us from returning.
+    let hits = Rc::new(Cell::new(0));
+    let hits2 = hits.clone();
+    let handle2 = handle.clone();
+    handle.spawn(futures::lazy(move || {
+        hits2.set(hits2.get() + 1);
+        handle2.spawn(futures::lazy(move || {
+            hits2.set(hits2.get() + 1);
+            Ok(())
+        }));
+        Ok(())
+    }));
+    let timeout = t!(Timeout::new(Duration::from_secs(10), &handle));
+    handle.spawn(timeout.then(|_| Ok(())));
+
+    let start = Instant::now();
+    l.run_until_idle();
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(hits.get(), 2);
+    assert_eq!(l.task_count(), 1);
+    assert_eq!(l.timeout_count(), 1);
+}
+
+#[test]
+fn run_until_empty() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let handle = l.handle();
+    assert_eq!(l.task_count(), 0);
+    assert_eq!(l.io_source_count(), 0);
+
+    let hits = Arc::new(AtomicUsize::new(0));
+    for i in 0..3 {
+        let timeout = t!(Timeout::new(Duration::from_millis(10 * i), &handle));
+        let hits = hits.clone();
+        handle.spawn(timeout.then(move |_| {
+            hits.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }));
+    }
+    let hits2 = hits.clone();
+    t!(l.remote().spawn(move |_| {
+        hits2.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }));
+    let socket = t!(UdpSocket::bind(&t!("127.0.0.1:0".parse()), &handle));
+    assert_eq!(l.task_count(), 3);
+    assert_eq!(l.io_source_count(), 1);
+    assert_eq!(l.timeout_count(), 3);
+
+    l.run_until_empty();
+    assert_eq!(hits.load(Ordering::SeqCst), 4);
+    assert_eq!(l.task_count(), 0);
+    assert_eq!(l.timeout_count(), 0);
+    drop(socket);
+}
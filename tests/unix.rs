@@ -0,0 +1,275 @@
+#![cfg(unix)]
+
+extern crate env_logger;
+extern crate futures;
+extern crate libc;
+#[macro_use]
+extern crate tokio_core;
+
+use std::env;
+use std::fs;
+use std::io;
+use std::net;
+use std::os::unix::prelude::*;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::thread;
+
+use futures::{future, Future, Poll};
+use futures::stream::Stream;
+use tokio_core::io::{read_to_end, write_all};
+use tokio_core::net::{TcpListener, UnixDatagram, UnixListener, UnixStream};
+use tokio_core::reactor::Core;
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+fn tmp_path() -> PathBuf {
+    static CNT: AtomicUsize = ATOMIC_USIZE_INIT;
+    let name = format!("tokio-core-unix-{}-{}", process::id(),
+                       CNT.fetch_add(1, Ordering::SeqCst));
+    let path = env::temp_dir().join(name);
+    drop(fs::remove_file(&path));
+    path
+}
+
+#[test]
+fn echo() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let handle = l.handle();
+    let path = tmp_path();
+    let srv = t!(UnixListener::bind(&path, &handle));
+
+    let server = srv.incoming().into_future().map_err(|e| e.0).and_then(|(s, _)| {
+        let (sock, _) = s.unwrap();
+        read_to_end(sock, Vec::new())
+    }).and_then(|(sock, buf)| {
+        write_all(sock, buf)
+    }).map(|_| ());
+    let client = UnixStream::connect(&path, &handle).and_then(|sock| {
+        write_all(sock, b"hello")
+    }).and_then(|(sock, _)| {
+        t!(sock.shutdown(net::Shutdown::Write));
+        read_to_end(sock, Vec::new())
+    });
+
+    let (_, (_, buf)) = t!(l.run(server.join(client)));
+    assert_eq!(buf, b"hello");
+    t!(fs::remove_file(&path));
+}
+
+#[test]
+fn connect_missing() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let path = tmp_path();
+    assert!(l.run(UnixStream::connect(&path, &l.handle())).is_err());
+}
+
+#[test]
+fn pair_peer_cred() {
+    drop(env_logger::init());
+    let l = t!(Core::new());
+    let (a, _b) = t!(UnixStream::pair(&l.handle()));
+    let cred = t!(a.peer_cred());
+    assert_eq!(cred.uid, unsafe { libc::getuid() });
+    assert_eq!(cred.gid, unsafe { libc::getgid() });
+    if cfg!(any(target_os = "linux", target_os = "android")) {
+        assert_eq!(cred.pid, Some(process::id() as libc::pid_t));
+    }
+}
+
+struct SendMessage {
+    socket: UnixDatagram,
+    path: PathBuf,
+}
+
+impl Future for SendMessage {
+    type Item = ();
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(), io::Error> {
+        let n = try_nb!(self.socket.send_to(b"1234", &self.path));
+        assert_eq!(n, 4);
+        Ok(().into())
+    }
+}
+
+struct RecvMessage {
+    socket: UnixDatagram,
+    expected_path: PathBuf,
+}
+
+impl Future for RecvMessage {
+    type Item = ();
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(), io::Error> {
+        let mut buf = [0; 32];
+        let (n, addr) = try_nb!(self.socket.recv_from(&mut buf));
+        assert_eq!(n, 4);
+        assert_eq!(&buf[..4], b"1234");
+        assert_eq!(addr.as_pathname(), Some(&*self.expected_path));
+        Ok(().into())
+    }
+}
+
+#[test]
+fn datagram() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let a_path = tmp_path();
+    let b_path = tmp_path();
+    let a = t!(UnixDatagram::bind(&a_path, &l.handle()));
+    let b = t!(UnixDatagram::bind(&b_path, &l.handle()));
+
+    let send = SendMessage { socket: a, path: b_path.clone() };
+    let recv = RecvMessage { socket: b, expected_path: a_path.clone() };
+    t!(l.run(send.join(recv)));
+    t!(fs::remove_file(&a_path));
+    t!(fs::remove_file(&b_path));
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[test]
+fn abstract_namespace() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let handle = l.handle();
+    let name = format!("tokio-core-abstract-{}", process::id());
+    let srv = t!(UnixListener::bind_abstract(name.as_bytes(), &handle));
+
+    let server = srv.incoming().into_future().map_err(|e| e.0).and_then(|(s, _)| {
+        let (sock, _) = s.unwrap();
+        write_all(sock, b"abstract")
+    }).map(|_| ());
+    let client = UnixStream::connect_abstract(name.as_bytes(), &handle)
+        .and_then(|sock| read_to_end(sock, Vec::new()));
+
+    let (_, (_, buf)) = t!(l.run(server.join(client)));
+    assert_eq!(buf, b"abstract");
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[test]
+fn abstract_datagram() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let handle = l.handle();
+    let name = format!("tokio-core-abstract-dgram-{}", process::id());
+    let a = t!(UnixDatagram::unbound(&handle));
+    let b = t!(UnixDatagram::bind_abstract(name.as_bytes(), &handle));
+
+    let send = future::poll_fn(|| {
+        let n = try_nb!(a.send_to_abstract(b"abstract", name.as_bytes()));
+        Ok::<_, io::Error>(n.into())
+    });
+    let mut buf = [0; 16];
+    let recv = future::poll_fn(|| {
+        let (n, _) = try_nb!(b.recv_from(&mut buf));
+        Ok(n.into())
+    });
+    let (_, n) = t!(l.run(send.join(recv)));
+    assert_eq!(&buf[..n], b"abstract");
+}
+
+#[test]
+fn connect_cloexec() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let path = tmp_path();
+    let srv = t!(UnixListener::bind(&path, &l.handle()));
+    let client = t!(l.run(UnixStream::connect(&path, &l.handle())));
+    let flags = unsafe { libc::fcntl(client.as_raw_fd(), libc::F_GETFD) };
+    assert!(flags & libc::FD_CLOEXEC != 0);
+    drop(srv);
+    t!(fs::remove_file(&path));
+}
+
+#[test]
+fn pass_tcp_listener() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let handle = l.handle();
+    let (a, b) = t!(UnixStream::pair(&handle));
+    let srv = t!(TcpListener::bind(&t!("127.0.0.1:0".parse()), &handle));
+    let addr = t!(srv.local_addr());
+
+    let send = future::poll_fn(|| {
+        let n = try_nb!(a.send_fds(b"x", &[srv.as_raw_fd()]));
+        assert_eq!(n, 1);
+        Ok::<_, io::Error>(().into())
+    });
+    let recv = future::poll_fn(|| {
+        let mut buf = [0; 8];
+        let mut fds = [-1; 4];
+        let (n, nfds) = try_nb!(b.recv_fds(&mut buf, &mut fds));
+        assert_eq!(&buf[..n], b"x");
+        assert_eq!(nfds, 1);
+        Ok(fds[0].into())
+    });
+    let (_, fd) = t!(l.run(send.join(recv)));
+    drop(srv);
+
+    let srv = t!(unsafe { TcpListener::from_raw_fd(fd, &handle) });
+    assert_eq!(t!(srv.local_addr()), addr);
+    let t = thread::spawn(move || {
+        net::TcpStream::connect(&addr).unwrap()
+    });
+    let (accepted, _) = t!(l.run(srv.incoming().into_future().map_err(|e| e.0)));
+    let theirs = t.join().unwrap();
+    let (mine, _) = accepted.unwrap();
+    assert_eq!(t!(mine.peer_addr()), t!(theirs.local_addr()));
+}
+
+#[test]
+fn recv_fds_without_fds() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let (a, b) = t!(UnixDatagram::pair(&l.handle()));
+    let recv = future::poll_fn(|| {
+        let mut buf = [0; 8];
+        let mut fds = [-1; 1];
+        let (n, nfds) = try_nb!(b.recv_fds(&mut buf, &mut fds));
+        assert_eq!(&buf[..n], b"hi");
+        assert_eq!(nfds, 0);
+        Ok::<_, io::Error>(().into())
+    });
+    let send = future::poll_fn(|| {
+        try_nb!(a.send_fds(b"hi", &[]));
+        Ok::<_, io::Error>(().into())
+    });
+    t!(l.run(send.join(recv)));
+}
+
+#[test]
+fn recv_fds_truncated() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let (a, b) = t!(UnixDatagram::pair(&l.handle()));
+    let send = future::poll_fn(|| {
+        try_nb!(a.send_fds(b"x", &[0, 1, 2]));
+        Ok::<_, io::Error>(().into())
+    });
+    t!(l.run(send));
+
+    let recv = future::poll_fn(|| {
+        let mut buf = [0; 8];
+        let mut fds = [-1; 1];
+        match b.recv_fds(&mut buf, &mut fds) {
+            Ok(_) => panic!("truncated descriptors weren't reported"),
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                Ok::<_, io::Error>(futures::Async::NotReady)
+            }
+            Err(e) => Ok(e.into()),
+        }
+    });
+    let err: io::Error = t!(l.run(recv));
+    assert_eq!(err.kind(), io::ErrorKind::Other);
+}